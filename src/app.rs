@@ -1,36 +1,70 @@
-use std::fmt;
-use std::sync::Arc;
-use std::path::PathBuf;
-use crate::internal::{self, BootResult, BootError, Directory};
-use crate::config::Config;
+use std::fs;
+use std::io::{self, Write};
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+use std::process;
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::thread;
+use crate::internal::{self, BootResult, BootError, Directory, Node, WalkOptions};
+use crate::platform::{self, Platform, TrashItem};
+use crate::config::{self, Config, ConfigModes};
+use crate::command::{Clip, Command, Registry, ShellError, ShellResult};
+use crate::preview::{self, HexRow, Preview, HEX_ROW_BYTES};
+use crate::fileops;
+use crate::clipboard;
+use crate::job::Job;
+use crate::search::{self, Found, Pattern};
+use crate::index::{self, Index, IndexState};
+use crate::opener::{self, Opener};
+use crate::highlight::{self, Highlight, Syntax};
+use crate::archive;
 
 pub enum CursorDirection {Right, Left}
 
+#[derive(Clone, Copy)]
 pub enum Mode {
     Normal,
     Ended,
     InsideShell,
     InsideOptions,
+    InsideTrash,
+    InsideSearch,
+    InsideFinder,
+    InsideViewer,
+    InsideRename,
+    InsideVisual,
 }
 
-impl fmt::Display for Mode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let str = match self {
-            Mode::Normal => "NOR".to_string(),
-            Mode::InsideShell => "SHL".to_string().to_string(),
-            Mode::InsideOptions => "OPS".to_string(),
-            Mode::Ended => "END".to_string(),
-        };
-        write!(f, "{}", str)
+impl Mode {
+    /// The mode bar label configured under `[modes]`.
+    pub fn label<'a>(&self, modes: &'a ConfigModes) -> &'a str {
+        match self {
+            Mode::Normal => &modes.normal,
+            Mode::InsideShell => &modes.shell,
+            Mode::InsideOptions => &modes.options,
+            Mode::InsideTrash => &modes.trash,
+            Mode::InsideSearch => &modes.search,
+            Mode::InsideFinder => &modes.finder,
+            Mode::InsideViewer => &modes.viewer,
+            Mode::InsideRename => &modes.rename,
+            Mode::InsideVisual => &modes.visual,
+            Mode::Ended => &modes.end,
+        }
     }
 }
 
+#[allow(dead_code)]
 pub struct AppOptions {
     pub open: bool,
     pub current: u8,
 }
 
-impl<'a> AppOptions {
+impl AppOptions {
     pub fn new() -> AppOptions {
         AppOptions {
             open: false,
@@ -39,9 +73,410 @@ impl<'a> AppOptions {
     }
 }
 
+pub struct AppHex {
+    pub enabled: bool,
+    pub scroll: u64,
+    pub page: u64,
+}
+
+impl AppHex {
+    fn new() -> AppHex {
+        AppHex {
+            enabled: false,
+            scroll: 0,
+            page: 1,
+        }
+    }
+}
+
+pub struct AppTrash {
+    pub items: Vec<TrashItem>,
+    pub selection_idx: Option<usize>,
+}
+
+impl AppTrash {
+    fn new() -> AppTrash {
+        AppTrash {
+            items: Vec::new(),
+            selection_idx: None,
+        }
+    }
+}
+
+// Terminals cap OSC 52 payloads, anything larger is unlikely to arrive whole anyway.
+const CLIPBOARD_MAX_BYTES: u64 = 1024 * 1024;
+
+// Larger files are rarely worth reading line by line, and too many matches are not worth reading at all.
+const GREP_MAX_BYTES: u64 = 16 * 1024 * 1024;
+const GREP_MAX_MATCHES: u64 = 10_000;
+// How much of the file is shown around a content match.
+const CONTEXT_BEFORE: usize = 5;
+const CONTEXT_LINES: usize = 200;
+// Nobody scrolls past the best few hundred fuzzy matches, and huge trees are better served by `ser`.
+const FINDER_MAX_SHOWN: usize = 500;
+const FINDER_MAX_FILES: usize = 200_000;
+// How deep `exp` lets folders be opened when no depth is given, and how many rows `exp <depth>` may read.
+const TREE_MAX_DEPTH: usize = 16;
+const TREE_MAX_ROWS: usize = 20_000;
+// Larger files take too long to read whole, they are better off in a real pager.
+const VIEWER_MAX_BYTES: u64 = 64 * 1024 * 1024;
+const VIEWER_SIDE_STEP: usize = 8;
+
+/// Paths yanked with `cpy`, pasted into the cwd with `p`.
+/// Cut paths are moved instead of copied.
+pub struct AppRegister {
+    pub paths: Vec<PathBuf>,
+    pub cut: bool,
+    /// Set while the cut paths are being moved by a paste.
+    moving: bool,
+}
+
+impl AppRegister {
+    fn new() -> AppRegister {
+        AppRegister {
+            paths: Vec::new(),
+            cut: false,
+            moving: false,
+        }
+    }
+
+    /// Once the paste moving the cut paths finished, nothing is left to paste again after it `moved` them all.
+    /// Otherwise whatever is still in place stays cut.
+    fn settle(&mut self, moved: bool) {
+        if !std::mem::take(&mut self.moving) {
+            return;
+        }
+        match moved {
+            true => self.paths.clear(),
+            false => self.paths.retain(|path| path.symlink_metadata().is_ok()),
+        }
+    }
+}
+
+/// The results of the last `ser` or `grp`, they keep streaming in while the search runs.
+pub struct AppSearch {
+    pub pattern: String,
+    pub root: PathBuf,
+    /// Set for `grp`, whose results point at lines inside files.
+    pub contents: bool,
+    pub results: Vec<Found>,
+    pub selection_idx: Option<usize>,
+    found: Receiver<Found>,
+    /// Set once the search stopped sending results.
+    finished: bool,
+    /// The result the context was read for, its first line number and the lines.
+    context: Option<(usize, usize, Vec<String>)>,
+}
+
+impl AppSearch {
+    fn new(pattern: String, root: PathBuf, contents: bool, found: Receiver<Found>) -> AppSearch {
+        AppSearch {
+            pattern,
+            root,
+            contents,
+            results: Vec::new(),
+            selection_idx: None,
+            found,
+            finished: false,
+            context: None,
+        }
+    }
+
+    /// Moves whatever was found since the last call into `results`.
+    pub fn collect(&mut self) {
+        loop {
+            match self.found.try_recv() {
+                Ok(found) => self.results.push(found),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                },
+            }
+        }
+        if self.selection_idx.is_none() && !self.results.is_empty() {
+            self.selection_idx = Some(0);
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Found> {
+        self.selection_idx.and_then(|idx| self.results.get(idx))
+    }
+
+    pub fn shift(&mut self, down: bool) {
+        let len = self.results.len();
+        self.selection_idx = match (self.selection_idx, down) {
+            _ if len == 0 => None,
+            (Some(k), true) if k + 1 < len => Some(k + 1),
+            (Some(_), true) => Some(0),
+            (Some(k), false) if k > 0 => Some(k - 1),
+            (Some(_), false) => Some(len - 1),
+            (None, _) => Some(0),
+        };
+    }
+}
+
+/// The files matching one query of the finder.
+struct Narrowed {
+    /// Every path matching it, in the order they were found.
+    candidates: Vec<usize>,
+    /// How many paths were looked at, later ones still need scoring.
+    seen: usize,
+    /// The best `FINDER_MAX_SHOWN` candidates and their score, best first.
+    best: Vec<(i64, usize)>,
+}
+
+/// The fuzzy finder over every file below the cwd, files keep streaming in while it is open.
+pub struct AppFinder {
+    pub query: String,
+    pub root: PathBuf,
+    /// Paths relative to `root`.
+    pub paths: Vec<String>,
+    /// Index into `paths` and the matched char positions, best first.
+    pub matches: Vec<(usize, Vec<usize>)>,
+    pub selection_idx: Option<usize>,
+    /// What every prefix of the query matched, the whole query last.
+    /// Typing only narrows down the last one, erasing goes back to the one before.
+    narrowed: Vec<Narrowed>,
+    found: Receiver<String>,
+    /// Stops the listing once the finder is closed.
+    closed: Arc<AtomicBool>,
+}
+
+/// The best `FINDER_MAX_SHOWN` of `scored`, best first.
+/// Shorter paths win ties, then the order files were found in.
+fn best_found(paths: &[String], scored: impl Iterator<Item = (i64, usize)>) -> Vec<(i64, usize)> {
+    let mut worst_first = BinaryHeap::with_capacity(FINDER_MAX_SHOWN + 1);
+    for (score, idx) in scored {
+        worst_first.push(Reverse((score, Reverse(paths[idx].len()), Reverse(idx))));
+        if worst_first.len() > FINDER_MAX_SHOWN {
+            worst_first.pop();
+        }
+    }
+    worst_first.into_sorted_vec().into_iter().map(|Reverse((score, _, Reverse(idx)))| (score, idx)).collect()
+}
+
+impl AppFinder {
+    fn new(root: PathBuf, found: Receiver<String>, closed: Arc<AtomicBool>) -> AppFinder {
+        AppFinder {
+            query: String::new(),
+            root,
+            paths: Vec::new(),
+            matches: Vec::new(),
+            selection_idx: None,
+            narrowed: vec![Narrowed { candidates: Vec::new(), seen: 0, best: Vec::new() }],
+            found,
+            closed,
+        }
+    }
+
+    /// Takes in the files listed since the last call.
+    pub fn collect(&mut self) {
+        let before: usize = self.paths.len();
+        self.paths.extend(self.found.try_iter());
+        if self.paths.len() > before {
+            self.catch_up();
+            self.show();
+        }
+    }
+
+    /// Scores the paths the current query has not seen yet, merging them into its best.
+    fn catch_up(&mut self) {
+        let current: &mut Narrowed = self.narrowed.last_mut().expect("the empty query is always there");
+        let fresh: Vec<(i64, usize)> = (current.seen..self.paths.len())
+            .filter_map(|idx| search::fuzzy(&self.query, &self.paths[idx]).map(|(score, _)| (score, idx)))
+            .collect();
+        current.seen = self.paths.len();
+        if fresh.is_empty() {
+            return;
+        }
+        current.candidates.extend(fresh.iter().map(|(_, idx)| *idx));
+        let best: Vec<(i64, usize)> = std::mem::take(&mut current.best);
+        current.best = best_found(&self.paths, best.into_iter().chain(fresh));
+    }
+
+    /// Lays the best of the current query out for the list, with the chars that matched.
+    fn show(&mut self) {
+        let current: &Narrowed = self.narrowed.last().expect("the empty query is always there");
+        self.matches = current.best.iter()
+            .filter_map(|(_, idx)| search::fuzzy(&self.query, &self.paths[*idx]).map(|(_, positions)| (*idx, positions)))
+            .collect();
+        self.selection_idx = match self.matches.len() {
+            0 => None,
+            len => Some(self.selection_idx.unwrap_or(0).min(len - 1)),
+        };
+    }
+
+    /// A longer query only matches what the shorter one did, so only those are scored again.
+    pub fn push(&mut self, c: char) {
+        self.catch_up();
+        self.query.push(c);
+        let previous: &Narrowed = self.narrowed.last().expect("the empty query is always there");
+        let scored: Vec<(i64, usize)> = previous.candidates.iter()
+            .filter_map(|&idx| search::fuzzy(&self.query, &self.paths[idx]).map(|(score, _)| (score, idx)))
+            .collect();
+        let narrowed = Narrowed {
+            candidates: scored.iter().map(|(_, idx)| *idx).collect(),
+            seen: previous.seen,
+            best: best_found(&self.paths, scored.into_iter()),
+        };
+        self.narrowed.push(narrowed);
+        self.selection_idx = None;
+        self.show();
+    }
+
+    pub fn pop(&mut self) {
+        if self.query.pop().is_none() {
+            return;
+        }
+        self.narrowed.pop();
+        self.catch_up();
+        self.selection_idx = None;
+        self.show();
+    }
+
+    pub fn selected(&self) -> Option<PathBuf> {
+        self.selection_idx
+            .and_then(|idx| self.matches.get(idx))
+            .map(|(path_idx, _)| self.root.join(&self.paths[*path_idx]))
+    }
+
+    pub fn shift(&mut self, down: bool) {
+        let len = self.matches.len();
+        self.selection_idx = match (self.selection_idx, down) {
+            _ if len == 0 => None,
+            (Some(k), true) if k + 1 < len => Some(k + 1),
+            (Some(_), true) => Some(0),
+            (Some(k), false) if k > 0 => Some(k - 1),
+            (Some(_), false) => Some(len - 1),
+            (None, _) => Some(0),
+        };
+    }
+}
+
+impl Drop for AppFinder {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A text file read whole for the viewer.
+pub struct AppViewer {
+    pub path: PathBuf,
+    pub encoding: &'static str,
+    pub lines: Vec<String>,
+    pub highlights: Option<Vec<Vec<Highlight>>>,
+    /// First line and first column on screen.
+    pub scroll: usize,
+    pub side: usize,
+    /// Lines on screen, known once drawn.
+    pub page: usize,
+    pub wrap: bool,
+    /// The search being typed after `/`.
+    pub typing: Option<String>,
+    pub query: String,
+    /// Lines holding the query, and which of them was jumped to last.
+    pub matches: Vec<usize>,
+    pub match_idx: Option<usize>,
+}
+
+impl AppViewer {
+    fn new(path: PathBuf, encoding: &'static str, lines: Vec<String>, highlights: Option<Vec<Vec<Highlight>>>) -> AppViewer {
+        AppViewer {
+            path,
+            encoding,
+            lines,
+            highlights,
+            scroll: 0,
+            side: 0,
+            page: 1,
+            wrap: false,
+            typing: None,
+            query: String::new(),
+            matches: Vec::new(),
+            match_idx: None,
+        }
+    }
+
+    pub fn scroll(&mut self, lines: i64) {
+        let last: usize = self.lines.len().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(lines as isize).min(last);
+    }
+
+    pub fn side_scroll(&mut self, right: bool) {
+        self.side = match right {
+            true => self.side + VIEWER_SIDE_STEP,
+            false => self.side.saturating_sub(VIEWER_SIDE_STEP),
+        };
+    }
+
+    /// Where `query` is found inside `line`, ignoring case unless the query has capitals.
+    pub fn find_in(&self, line: &str) -> Vec<(usize, usize)> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+        match self.query.chars().any(char::is_uppercase) {
+            true => line.match_indices(&self.query).map(|(at, m)| (at, at + m.len())).collect(),
+            // Lowercasing may change byte lengths, so only ASCII is folded here.
+            false => line.to_ascii_lowercase().match_indices(&self.query.to_ascii_lowercase())
+                .map(|(at, m)| (at, at + m.len()))
+                .collect(),
+        }
+    }
+
+    /// Searches the file for what was typed and jumps to the first match from the top of the screen.
+    fn search(&mut self) {
+        self.query = self.typing.take().unwrap_or_default();
+        self.matches = (0..self.lines.len()).filter(|&i| !self.find_in(&self.lines[i]).is_empty()).collect();
+        self.match_idx = self.matches.iter().position(|&line| line >= self.scroll)
+            .or((!self.matches.is_empty()).then_some(0));
+        if let Some(idx) = self.match_idx {
+            self.scroll = self.matches[idx];
+        }
+    }
+
+    /// Jumps to the next or previous match, wrapping around the file.
+    fn next_match(&mut self, forward: bool) {
+        let len: usize = self.matches.len();
+        self.match_idx = match (self.match_idx, forward) {
+            _ if len == 0 => None,
+            (Some(k), true) => Some((k + 1) % len),
+            (Some(k), false) => Some((k + len - 1) % len),
+            (None, _) => Some(0),
+        };
+        if let Some(idx) = self.match_idx {
+            self.scroll = self.matches[idx];
+        }
+    }
+}
+
+pub enum Feedback {
+    Info(String),
+    Error(String),
+}
+
+/// An action waiting for the user to confirm it.
+pub enum Pending {
+    Delete { target: PathBuf, force: bool },
+    DeleteMarked { targets: Vec<PathBuf>, force: bool },
+    Purge(TrashItem),
+    EmptyTrash,
+    Move { src: PathBuf, dst: PathBuf },
+    /// The renames `brn` was asked for, and the order that carries them out.
+    BulkRename { renames: Vec<(PathBuf, PathBuf)>, steps: Vec<(PathBuf, PathBuf)> },
+}
+
+pub struct Prompt {
+    pub question: String,
+    pub pending: Pending,
+}
+
 pub struct AppShell {
     pub input: String,
     pub cursor_position: usize,
+    pub feedback: Option<Feedback>,
+    /// Where editing may begin, the shell keeps its leading `:`.
+    start: usize,
 }
 
 impl AppShell {
@@ -49,6 +484,18 @@ impl AppShell {
         AppShell {
             input: ":".to_string(),
             cursor_position: 1,
+            feedback: None,
+            start: 1,
+        }
+    }
+
+    /// An editor over `text`, with the cursor at its end.
+    fn editing(text: &str) -> AppShell {
+        AppShell {
+            input: text.to_string(),
+            cursor_position: text.len(),
+            feedback: None,
+            start: 0,
         }
     }
 
@@ -56,133 +503,1867 @@ impl AppShell {
         self.input.insert(self.cursor_position, pressed)
     }
 
+    /// Removes the char before the cursor.
     fn delete(&mut self) {
-        if self.cursor_position > 1 {
-            match self.input.pop() {
-                Some(_) => self.cursor_shift_left(),
-                _ => {}, 
-            }
+        if self.cursor_position > self.start {
+            self.cursor_shift_left();
+            self.input.remove(self.cursor_position);
         }
     }
 
     fn cursor_shift_left(&mut self) {
-        let new_cursor_pos: usize = self.cursor_position.saturating_sub(1);
-        self.cursor_position = new_cursor_pos.clamp(0, self.input.len());
+        let previous: Option<usize> = self.input[..self.cursor_position].char_indices().last().map(|(i, _)| i);
+        self.cursor_position = previous.unwrap_or(0).max(self.start);
     }
 
     fn cursor_shift_right(&mut self) {
-        let new_cursor_pos: usize = self.cursor_position.saturating_add(1);
-        self.cursor_position = new_cursor_pos.clamp(0, self.input.len());
+        let next: Option<usize> = self.input[self.cursor_position..].chars().next().map(|c| self.cursor_position + c.len_utf8());
+        self.cursor_position = next.unwrap_or(self.input.len());
     }
 }
 
+/// The inline editor over the Name cell of the selected row.
+pub struct AppRename {
+    pub target: PathBuf,
+    pub editor: AppShell,
+}
+
 pub struct App {
     pub config: Config,
+    pub commands: Registry,
     pub mode: Mode,
     pub shell: AppShell, 
+    #[allow(dead_code)]
     pub options: AppOptions,
     pub cwd: Directory,
-    pub selection_idx: Option<usize>
+    pub selection_idx: Option<usize>,
+    pub home: PathBuf,
+    pub previous_dir: Option<Arc<PathBuf>>,
+    pub preview: Option<(Arc<PathBuf>, Preview)>,
+    pub hex: AppHex,
+    pub prompt: Option<Prompt>,
+    pub trash: AppTrash,
+    pub job: Option<Job>,
+    pub register: AppRegister,
+    pub search: Option<AppSearch>,
+    pub finder: Option<AppFinder>,
+    pub viewer: Option<AppViewer>,
+    pub rename: Option<AppRename>,
+    /// Entries marked for the next file operation, they stay marked across folders.
+    pub marked: BTreeSet<PathBuf>,
+    /// Where the visual range started.
+    pub visual_anchor: Option<usize>,
+    pub index: Arc<Mutex<IndexState>>,
+    /// A terminal program waiting for the screen, run by the main loop.
+    pub foreground: Option<process::Command>,
+    /// The list `brn` handed to the editor, with the entries it was written from.
+    renaming_list: Option<(PathBuf, Vec<PathBuf>)>,
+    /// The folder `exp <depth>` is reading in the background, how deep, and where the tree arrives.
+    tree_walk: Option<(PathBuf, usize, Receiver<Vec<Node>>)>,
+    /// Where the shell goes back to once a command ran or was given up.
+    shell_from: Mode,
+    /// The operating system behind opening, trashing and the clipboard.
+    platform: Box<dyn Platform>,
 }
 
 impl App {
     pub fn new() -> BootResult<Self> {
-        let cnf = Config::new().unwrap();
+        Self::with_platform(Box::new(platform::native()))
+    }
 
-        let _drives: Arc<Vec<PathBuf>> = Arc::new(
-            match internal::drives() {
-                Ok(drives) => drives,
-                Err(e) => { return Err(BootError::DriveLoadingFailed(e)); }
-            }
-        ); 
+    /// Boots on `host`, which every later call to the operating system goes through.
+    pub fn with_platform(host: Box<dyn Platform>) -> BootResult<Self> {
+        let cnf = Config::new().map_err(|_| BootError::ConfigLoadingFailed)?;
 
-        let cwd: Arc<PathBuf> = Arc::new(PathBuf::from(
-            match internal::home_drive() {
-                Ok(drive) => drive,
-                Err(e) => { return Err(BootError::DriveLoadingFailed(e)); }
-            }
-        ));
+        let home: PathBuf = match host.home() {
+            Ok(home) => home,
+            Err(e) => { return Err(BootError::DriveLoadingFailed(e)); }
+        };
+        let cwd: Directory = Directory::from(Arc::new(home.to_owned()))
+            .map_err(|e| BootError::DriveLoadingFailed(e.into()))?;
+
+        let index: Arc<Mutex<IndexState>> = Arc::new(Mutex::new(IndexState::Missing));
+        index::refresh_in_background(index::location(&home), Arc::clone(&index));
 
         let app = Self {
+            commands: Registry::new(&cnf.commands),
             config: cnf,
             mode: Mode::Normal,
             shell: AppShell::new(),
             options: AppOptions::new(),
-            cwd: Directory::from(cwd),
-            selection_idx: Some(0),
+            selection_idx: if cwd.is_empty() { None } else { Some(0) },
+            cwd,
+            home,
+            previous_dir: None,
+            preview: None,
+            hex: AppHex::new(),
+            prompt: None,
+            trash: AppTrash::new(),
+            job: None,
+            register: AppRegister::new(),
+            search: None,
+            finder: None,
+            viewer: None,
+            rename: None,
+            marked: BTreeSet::new(),
+            visual_anchor: None,
+            index,
+            foreground: None,
+            renaming_list: None,
+            tree_walk: None,
+            shell_from: Mode::Normal,
+            platform: host,
         };
 
         Ok(app)
     }
 
     pub fn format_mode(&mut self) -> String {
-        let app_mode: String = self.mode.to_string();
+        let app_mode: &str = self.mode.label(&self.config.modes);
+        let location: String = match self.mode {
+            Mode::InsideTrash => format!("{} items in trash", self.trash.items.len()),
+            Mode::InsideSearch => match &self.search {
+                Some(search) => format!("{} matches for {} in {}", search.results.len(), search.pattern, search.root.display()),
+                None => String::new(),
+            },
+            Mode::InsideViewer => match &self.viewer {
+                Some(viewer) => {
+                    let position = format!("{} [{}] line {} of {}{}", viewer.path.display(), viewer.encoding,
+                                           viewer.scroll + 1, viewer.lines.len(), if viewer.wrap { ", wrapped" } else { "" });
+                    match (viewer.matches.len(), viewer.match_idx) {
+                        _ if viewer.query.is_empty() => position,
+                        (0, _) => format!("{position}   no match for {}", viewer.query),
+                        (len, idx) => format!("{position}   match {} of {len} for {}", idx.map_or(0, |i| i + 1), viewer.query),
+                    }
+                },
+                None => String::new(),
+            },
+            Mode::InsideFinder => match &self.finder {
+                Some(finder) => format!("{} of {} files in {}", finder.matches.len(), finder.paths.len(), finder.root.display()),
+                None => String::new(),
+            },
+            _ => self.cwd.display().to_string(),
+        };
+        let location: String = match self.marked.len() {
+            0 => location,
+            marked => format!("{location}   [{marked} marked]"),
+        };
+        let location: String = match &self.job {
+            Some(job) => format!("{location}   [{}]", job.status()),
+            None => location,
+        };
         let formatted: String = format!("{:spacing_before$}{app_mode}{:spacing_between$}{}",
-                                        "", "", self.cwd.display(), spacing_before=1, spacing_between=3);
+                                        "", "", location, spacing_before=1, spacing_between=3);
         formatted
     }
     
+    /// Opens the shell, a command typed in the viewer goes back to it once done.
+    pub fn open_shell(&mut self) {
+        self.shell_from = match self.mode {
+            mode @ (Mode::InsideViewer | Mode::InsideTrash) => mode,
+            _ => Mode::Normal,
+        };
+        self.mode = Mode::InsideShell;
+    }
+
+    pub fn close_shell(&mut self) {
+        self.mode = self.shell_from;
+        self.reset_shell();
+        self.shell.feedback = None;
+    }
+
+    /// The mode whose view is on screen, the shell keeps showing where it was opened from.
+    pub fn shown_mode(&self) -> Mode {
+        match self.mode {
+            Mode::InsideShell => self.shell_from,
+            mode => mode,
+        }
+    }
+
     pub fn execute_shell(&mut self) {
-        if self.shell.input == ":end" {
-            self.mode = Mode::Ended;
+        let executed = self.commands.parse(&self.shell.input)
+            .and_then(|command| self.dispatch(command));
+        match executed {
+            Ok(_) => {
+                self.reset_shell();
+                if let Mode::InsideShell = self.mode {
+                    self.mode = self.shell_from;
+                }
+                // The command may have moved something into the trash.
+                if let Mode::InsideTrash = self.mode {
+                    if let Err(e) = self.reload_trash() {
+                        self.shell.feedback = Some(Feedback::Error(e.to_string()));
+                    }
+                }
+            },
+            // Keep the input around so it can be fixed.
+            Err(e) => self.shell.feedback = Some(Feedback::Error(e.to_string())),
         }
-        else if self.shell.input == ":exp" {
-            // TODO: explore all dirs of cwd.
+    }
+
+    fn dispatch(&mut self, command: Command) -> ShellResult<()> {
+        match command {
+            Command::End => {
+                if let Some(job) = &self.job {
+                    return Err(ShellError::Failed(format!("{} is still running, press Esc to cancel it", job.label)));
+                }
+                self.mode = Mode::Ended;
+                Ok(())
+            },
+            Command::Exp { depth } => self.exp(depth),
+            Command::Ser { pattern, regex, walk } => self.ser(pattern, regex, walk),
+            Command::Grp { pattern, regex } => self.grp(pattern, regex),
+            Command::New { target, template } => self.new_file(&target, template),
+            Command::Mkd { target } => self.mkd(&target),
+            Command::Brn => self.brn(),
+            Command::Zip { archive } => self.zip(archive),
+            Command::Unz { archive, destination } => self.unz(archive, destination),
+            Command::Scd { target } => self.scd(&target),
+            Command::Del { target: Some(target), force } => self.del(&target, force),
+            Command::Del { target: None, force } => self.del_marked_or_selected(force),
+            Command::Cpy { targets, clip: Some(clip), .. } => self.clip(clip, &targets),
+            Command::Cpy { targets, cut, clip: None } => self.cpy(&targets, cut),
+            Command::Opn { target } => self.opn(target),
+            Command::Mov { target: Some(target), destination } => self.mov(&target, &destination),
+            Command::Mov { target: None, destination } => self.mov_marked_or_selected(&destination),
+            Command::Idx { build: false, .. } => self.inspect_index(),
+            Command::Idx { build: true, target } => self.build_index(target.as_deref()),
         }
-        else {
-            self.shell.input = String::from("Unknown Command");
-        }   
     }
     
-    pub fn append_to_shell(&mut self, pressed: char) {
-        if pressed == ':' {
-            self.reset_shell();
-        } else { 
-            self.shell.append(pressed);
-            self.shell.cursor_shift_right();
+    /// Expands `~` into the home directory and anchors relative paths at the cwd.
+    fn resolve(&self, target: &Path) -> PathBuf {
+        let expanded: PathBuf = match target.strip_prefix("~") {
+            Ok(rest) => self.home.join(rest),
+            Err(_) => target.to_path_buf(),
+        };
+        internal::normalize(&self.cwd.path().join(expanded))
+    }
+
+    /// Replaces the cwd with `target`.
+    /// When ascending, the directory we came from stays selected.
+    pub fn change_dir(&mut self, target: PathBuf) -> io::Result<()> {
+        let mut directory: Directory = Directory::from(Arc::new(target))?;
+        directory.set_tree(self.cwd.tree_depth());
+        let previous: Directory = std::mem::replace(&mut self.cwd, directory);
+        let came_from: Option<&Path> = previous.path().ancestors()
+            .find(|a| a.parent() == Some(self.cwd.path().as_path()));
+
+        self.selection_idx = match self.cwd.is_empty() {
+            true => None,
+            false => Some(came_from.and_then(|p| self.cwd.position(p)).unwrap_or(0)),
+        };
+        self.previous_dir = Some(previous.path().to_owned());
+        Ok(())
+    }
+
+    /// Reads the cwd again after it was changed on disk.
+    /// Falls back to the closest ancestor when the cwd itself is gone.
+    pub fn reload(&mut self) {
+        let current: Arc<PathBuf> = self.cwd.path().to_owned();
+        let reloaded = current.ancestors()
+            .find_map(|dir| Directory::from(Arc::new(dir.to_path_buf())).ok());
+        if let Some(mut directory) = reloaded {
+            directory.restore(self.cwd.tree_depth(), &self.cwd.expanded());
+            self.cwd = directory;
         }
+        self.marked.retain(|path| path.symlink_metadata().is_ok());
+        self.selection_idx = match self.cwd.is_empty() {
+            true => None,
+            false => Some(self.selection_idx.unwrap_or(0).min(self.cwd.len() - 1)),
+        };
+        self.preview = None;
     }
 
-    pub fn delete_from_shell(&mut self) {
-        self.shell.delete();
+    pub fn selected(&self) -> Option<&Node> {
+        self.selection_idx.and_then(|idx| self.cwd.get(idx))
     }
 
-    pub fn reset_shell(&mut self) {
-        self.shell.input = ":".to_string();
+    /// Loads the preview of the selected node, unless it is already loaded.
+    pub fn refresh_preview(&mut self) {
+        let node: &Node = match self.selected() {
+            Some(node) => node,
+            None => {
+                self.preview = None;
+                return;
+            }
+        };
+        if let Some((path, _)) = &self.preview {
+            if path == &node.root_path {
+                return;
+            }
+        }
+        let preview = Preview::load(node, &self.config.preview);
+        self.preview = Some((node.root_path.to_owned(), preview));
+        self.hex.scroll = 0;
     }
 
-    pub fn move_cursor(&mut self, direction: CursorDirection) {
-        match direction {
-            CursorDirection::Left => self.shell.cursor_shift_left(),
-            CursorDirection::Right => self.shell.cursor_shift_right(),
+    fn previewing_binary(&self) -> bool {
+        matches!(self.preview, Some((_, Preview::Binary(_))))
+    }
+
+    pub fn toggle_hex(&mut self) {
+        self.hex.enabled = !self.hex.enabled;
+        self.hex.scroll = 0;
+    }
+
+    /// Scrolls the hex dump by `rows`, negative values scroll up.
+    pub fn scroll_hex(&mut self, rows: i64) {
+        if !self.hex.enabled || !self.previewing_binary() {
+            return;
         }
+        let size: u64 = self.selected().map(|n| n.size).unwrap_or(0);
+        let last_row: u64 = size.saturating_sub(1) / HEX_ROW_BYTES;
+        self.hex.scroll = self.hex.scroll.saturating_add_signed(rows).min(last_row);
     }
 
-    pub fn shift_down(&mut self) {
-        let i = match self.selection_idx {
-            Some(k) => {
-                let dir = self.cwd.walk();
-                if k < dir.len() {
-                    k + 1
-                } else {
-                    0
+    pub fn scroll_hex_page(&mut self, down: bool) {
+        let page = self.hex.page as i64;
+        self.scroll_hex(if down { page } else { -page });
+    }
+
+    /// The visible part of the hex dump, read straight from disk.
+    pub fn hex_window(&self, rows: usize) -> Option<io::Result<Vec<HexRow>>> {
+        match &self.preview {
+            Some((path, Preview::Binary(_))) if self.hex.enabled => {
+                Some(preview::hex_window(path, self.hex.scroll, rows))
+            },
+            _ => None,
+        }
+    }
+
+    /// Enters the selected directory, or opens the selected file.
+    pub fn descend(&mut self) {
+        let (path, is_dir) = match self.selected() {
+            Some(node) => (node.root_path.to_path_buf(), node.is_dir),
+            None => return,
+        };
+        let outcome = match is_dir {
+            true => self.change_dir(path.to_owned()).map_err(|e| e.to_string()),
+            false => self.open(&path),
+        };
+        if let Err(e) = outcome {
+            self.shell.feedback = Some(Feedback::Error(format!("{}: {e}", path.display())));
+        }
+    }
+
+    /// Shows the cwd as a tree, `depth` levels of it expanded right away, or flat again.
+    fn exp(&mut self, depth: Option<usize>) -> ShellResult<()> {
+        let selected: Option<PathBuf> = self.selected().map(|node| node.root_path.to_path_buf());
+        match (self.cwd.tree_depth(), depth) {
+            (Some(_), None) => self.cwd.set_tree(None),
+            (None, None) => self.cwd.set_tree(Some(TREE_MAX_DEPTH)),
+            (_, Some(depth)) => return self.read_tree(depth),
+        }
+        self.selection_idx = match self.cwd.is_empty() {
+            true => None,
+            false => selected.and_then(|path| self.cwd.position(&path)).or(Some(0)),
+        };
+        Ok(())
+    }
+
+    /// Reads the tree of the cwd `depth` levels deep in the background, `Esc` stops it.
+    fn read_tree(&mut self, depth: usize) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let root: PathBuf = self.cwd.path().to_path_buf();
+        let walked: PathBuf = root.clone();
+        let (sender, tree) = mpsc::channel();
+        let label: String = format!("Expanding {}", root.file_name().unwrap_or(root.as_os_str()).to_string_lossy());
+
+        self.job = Some(Job::spawn(label, move |reporter| {
+            let read = internal::read_tree(&walked, depth, TREE_MAX_ROWS, &|count| {
+                if count % 1000 == 0 {
+                    reporter.progress(count as u64, 0);
                 }
+                !reporter.cancelled()
+            });
+            match read {
+                Some(nodes) => {
+                    let rows: usize = nodes.len();
+                    let _ = sender.send(nodes);
+                    Ok(format!("Expanded {rows} rows {depth} levels deep"))
+                },
+                None if reporter.cancelled() => Err("Expanding cancelled".to_string()),
+                None => Err(format!("More than {TREE_MAX_ROWS} rows within {depth} levels, try fewer")),
             }
-            None => 0
+        }));
+        self.tree_walk = Some((root, depth, tree));
+        Ok(())
+    }
+
+    /// Shows the tree `read_tree` sent, unless the cwd changed in the meantime.
+    fn show_read_tree(&mut self) {
+        let (root, depth, tree) = match self.tree_walk.take() {
+            Some(walk) => walk,
+            None => return,
         };
-        self.selection_idx = Some(i);
+        let nodes: Vec<Node> = match tree.try_recv() {
+            Ok(nodes) if **self.cwd.path() == root => nodes,
+            _ => return,
+        };
+        let selected: Option<PathBuf> = self.selected().map(|node| node.root_path.to_path_buf());
+        self.cwd.set_tree_nodes(depth, nodes);
+        self.selection_idx = match self.cwd.is_empty() {
+            true => None,
+            false => selected.and_then(|path| self.cwd.position(&path)).or(Some(0)),
+        };
+        self.preview = None;
     }
 
-    pub fn shift_up(&mut self) {
-        let i = match self.selection_idx {
-            Some(k) => {
-                if k > 0 {
-                    k - 1
-                } else {
-                    let dir = self.cwd.walk();
-                    dir.len() - 1
-                }
+    /// Expands or collapses the selected folder of the tree view.
+    pub fn toggle_folder(&mut self) {
+        let (idx, path, expanded) = match (self.selection_idx, self.selected()) {
+            (Some(idx), Some(node)) if node.is_dir => (idx, node.root_path.to_path_buf(), node.expanded),
+            _ => return,
+        };
+        if self.cwd.tree_depth().is_none() {
+            self.shell.feedback = Some(Feedback::Error(format!("Folders expand in the tree view, :{} shows it", self.config.commands.exp)));
+            return;
+        }
+        match expanded {
+            true => self.cwd.collapse(idx),
+            false => match self.cwd.expand(idx) {
+                Ok(true) => {},
+                Ok(false) => self.shell.feedback = Some(Feedback::Info(format!("{} is as deep as the tree goes", path.display()))),
+                Err(e) => self.shell.feedback = Some(Feedback::Error(format!("{}: {e}", path.display()))),
+            },
+        }
+    }
+
+    fn opn(&mut self, target: Option<PathBuf>) -> ShellResult<()> {
+        let path: PathBuf = match target {
+            Some(target) => match self.resolve(&target) {
+                path if path.exists() => path,
+                _ => return Err(ShellError::InvalidArgument {
+                    argument: target.display().to_string(),
+                    reason: "does not exist".to_string()
+                }),
+            },
+            None => self.selected().map(|node| node.root_path.to_path_buf())
+                .ok_or(ShellError::Failed("Nothing to open".to_string()))?,
+        };
+        let found: Option<Opener> = opener::find(&self.config.openers, &path)
+            .map_err(|e| ShellError::Failed(format!("{}: {e}", path.display())))?;
+        // Text files nothing is configured for are read in the viewer instead of leaving Owl.
+        if found.is_none() && path.is_file() && opener::is_text(&path) {
+            return self.view(path).map_err(ShellError::Failed);
+        }
+        self.launch(found, &path).map_err(|e| ShellError::Failed(format!("{}: {e}", path.display())))
+    }
+
+    /// Opens `path` with the opener configured for it, or the system default.
+    fn open(&mut self, path: &Path) -> Result<(), String> {
+        let found: Option<Opener> = opener::find(&self.config.openers, path).map_err(|e| e.to_string())?;
+        self.launch(found, path)
+    }
+
+    fn launch(&mut self, found: Option<Opener>, path: &Path) -> Result<(), String> {
+        match found {
+            Some(Opener::Terminal(command)) => self.foreground = Some(command),
+            Some(Opener::Gui(command)) => opener::spawn_detached(command).map_err(|e| e.to_string())?,
+            None => self.platform.open(path).map_err(|e| e.to_string())?,
+        }
+        Ok(())
+    }
+
+    /// Called once a terminal program gave the screen back, it may have changed the cwd.
+    pub fn foreground_done(&mut self, outcome: io::Result<process::ExitStatus>) {
+        let failed: Option<String> = match outcome {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("The opener exited with {status}")),
+            Err(e) => Some(format!("The opener failed to start, {e}")),
+        };
+        self.reload();
+        if let Some((list, originals)) = self.renaming_list.take() {
+            let planned = match &failed {
+                Some(e) => Err(e.to_owned()),
+                None => fs::read_to_string(&list).map_err(|e| e.to_string())
+                    .and_then(|edited| self.plan_bulk_rename(&edited, originals)),
+            };
+            let _ = fs::remove_file(&list);
+            if let Err(e) = planned {
+                self.shell.feedback = Some(Feedback::Error(format!("Nothing renamed, {e}")));
+            }
+            return;
+        }
+        if let Some(e) = failed {
+            self.shell.feedback = Some(Feedback::Error(e));
+        }
+    }
+
+    /// Writes the names of the cwd to a file and hands it to `$EDITOR`, each edited line renames its entry.
+    fn brn(&mut self) -> ShellResult<()> {
+        let originals: Vec<PathBuf> = (0..self.cwd.len())
+            .filter_map(|idx| self.cwd.get(idx))
+            .filter(|node| node.depth == 0)
+            .map(|node| node.root_path.to_path_buf())
+            .collect();
+        if originals.is_empty() {
+            return Err(ShellError::Failed("Nothing to rename".to_string()));
+        }
+        let mut names = String::new();
+        for path in &originals {
+            match path.file_name().and_then(|n| n.to_str()).filter(|n| !n.contains('\n')) {
+                Some(name) => names.push_str(&format!("{name}\n")),
+                None => return Err(ShellError::Failed(format!("{} cannot be written as one line", path.display()))),
+            }
+        }
+        let (list, mut file) = fileops::create_temp("owl-rename", "txt").map_err(|e| ShellError::Failed(e.to_string()))?;
+        let editor = file.write_all(names.as_bytes()).and_then(|_| opener::editor(&list));
+        drop(file);
+        let editor = editor.map_err(|e| {
+            let _ = fs::remove_file(&list);
+            ShellError::Failed(e.to_string())
+        })?;
+        self.foreground = Some(editor);
+        self.renaming_list = Some((list, originals));
+        Ok(())
+    }
+
+    /// Checks the edited names line by line and asks before renaming anything.
+    fn plan_bulk_rename(&mut self, edited: &str, originals: Vec<PathBuf>) -> Result<(), String> {
+        let mut names: Vec<&str> = edited.lines().collect();
+        while names.len() > originals.len() && names.last() == Some(&"") {
+            names.pop();
+        }
+        if names.len() != originals.len() {
+            return Err(format!("there should be one name per line, {} lines for {} entries", names.len(), originals.len()));
+        }
+        let renames: Vec<(PathBuf, PathBuf)> = originals.iter().zip(&names)
+            .filter(|(path, name)| path.file_name().is_some_and(|n| n != **name))
+            .map(|(path, name)| (path.to_owned(), path.with_file_name(name)))
+            .collect();
+        if renames.is_empty() {
+            self.shell.feedback = Some(Feedback::Info("No name was changed".to_string()));
+            return Ok(());
+        }
+        for (i, name) in names.iter().enumerate() {
+            if let Some(reason) = internal::invalid_name(name) {
+                return Err(format!("'{name}' {reason}"));
+            }
+            if names[..i].contains(name) {
+                return Err(format!("'{name}' is given twice"));
+            }
+        }
+        if let Some((_, taken)) = renames.iter().find(|(_, to)| !originals.contains(to) && to.symlink_metadata().is_ok()) {
+            return Err(format!("{} already exists", taken.display()));
+        }
+        let (steps, cycles) = fileops::order_renames(&renames);
+        let through = match cycles {
+            0 => String::new(),
+            1 => ", a swap or cycle goes through a temporary name".to_string(),
+            n => format!(", {n} swaps or cycles go through temporary names"),
+        };
+        self.prompt = Some(Prompt {
+            question: format!("Rename {} entries as listed{through}? [y/n]", renames.len()),
+            pending: Pending::BulkRename { renames, steps },
+        });
+        Ok(())
+    }
+
+    fn bulk_rename(&mut self, count: usize, steps: Vec<(PathBuf, PathBuf)>) -> ShellResult<()> {
+        let total: usize = steps.len();
+        for (done, (from, to)) in steps.iter().enumerate() {
+            if let Err(e) = fs::rename(from, to) {
+                // Every step done so far is undone backwards, so the names end up as they were.
+                let stuck: Vec<String> = steps[..done].iter().rev()
+                    .filter(|(from, to)| fs::rename(to, from).is_err())
+                    .map(|(from, to)| format!("{} is still {}", from.display(), to.display()))
+                    .collect();
+                self.reload();
+                let failed = format!("{}: {e}", from.display());
+                return Err(ShellError::Failed(match stuck.is_empty() {
+                    true => format!("Nothing renamed, {failed}"),
+                    false => format!("Stopped after {done} of {total} steps, {failed}, and {}", stuck.join(", ")),
+                }));
             }
+        }
+        self.reload();
+        self.shell.feedback = Some(Feedback::Info(format!("Renamed {count} entries")));
+        Ok(())
+    }
+
+    /// Goes back up to the parent of the cwd.
+    pub fn ascend(&mut self) {
+        let parent: PathBuf = match self.cwd.path().parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => return,
+        };
+        if let Err(e) = self.change_dir(parent.to_owned()) {
+            self.shell.feedback = Some(Feedback::Error(format!("{}: {e}", parent.display())));
+        }
+    }
+
+    fn del(&mut self, target: &Path, force: bool) -> ShellResult<()> {
+        let path: PathBuf = self.resolve(target);
+        let md = fs::symlink_metadata(&path).map_err(|_| ShellError::InvalidArgument {
+            argument: target.display().to_string(),
+            reason: "does not exist".to_string()
+        })?;
+        if path.parent().is_none() {
+            return Err(ShellError::Failed(format!("Refusing to delete {}", path.display())));
+        }
+        if md.is_dir() {
+            let how = if force { "permanently" } else { "to trash" };
+            self.prompt = Some(Prompt {
+                question: format!("Delete folder {} and everything in it {how}? [y/n]", path.display()),
+                pending: Pending::Delete { target: path, force },
+            });
+            return Ok(());
+        }
+        self.delete(path, force)
+    }
+
+    fn del_marked_or_selected(&mut self, force: bool) -> ShellResult<()> {
+        let targets: Vec<PathBuf> = self.marked_or_selected("Nothing to delete")?;
+        if let [target] = targets.as_slice() {
+            let target = target.to_owned();
+            return self.del(&target, force).inspect(|_| self.marked.clear());
+        }
+        let how = if force { "permanently" } else { "to trash" };
+        self.prompt = Some(Prompt {
+            question: format!("Delete {} marked entries and everything in them {how}? [y/n]", targets.len()),
+            pending: Pending::DeleteMarked { targets, force },
+        });
+        Ok(())
+    }
+
+    fn delete_marked(&mut self, targets: Vec<PathBuf>, force: bool) -> ShellResult<()> {
+        let total: usize = targets.len();
+        for (done, path) in targets.iter().enumerate() {
+            let deleted = match force {
+                true => fileops::remove_tree(path).map_err(|e| e.to_string()),
+                false => self.platform.trash(path).map_err(|e| e.to_string()),
+            };
+            if let Err(e) = deleted {
+                self.reload();
+                return Err(ShellError::Failed(format!("Stopped after {done} of {total}, {}: {e}", path.display())));
+            }
+        }
+        self.marked.clear();
+        self.reload();
+        self.shell.feedback = Some(Feedback::Info(match force {
+            true => format!("Deleted {total} entries"),
+            false => format!("Moved {total} entries to trash"),
+        }));
+        Ok(())
+    }
+
+    fn delete(&mut self, path: PathBuf, force: bool) -> ShellResult<()> {
+        let deleted = match force {
+            true => fileops::remove_tree(&path).map_err(|e| e.to_string()),
+            false => self.platform.trash(&path).map_err(|e| e.to_string()),
+        };
+        deleted.map_err(|e| ShellError::Failed(format!("{}: {e}", path.display())))?;
+        self.reload();
+
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+        self.shell.feedback = Some(Feedback::Info(match force {
+            true => format!("Deleted {name}"),
+            false => format!("Moved {name} to trash"),
+        }));
+        Ok(())
+    }
+
+    /// Runs or drops the pending action, any other answer keeps asking.
+    pub fn answer_prompt(&mut self, answer: char) {
+        let prompt: Prompt = match self.prompt.take() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        let outcome = match (prompt.pending, answer.to_ascii_lowercase()) {
+            (Pending::Delete { target, force }, 'y') => self.delete(target, force),
+            (Pending::DeleteMarked { targets, force }, 'y') => self.delete_marked(targets, force),
+            (Pending::Purge(item), 'y') => self.purge(&item),
+            (Pending::EmptyTrash, 'y') => self.empty_trash(),
+            (Pending::Move { src, dst }, 'o') => self.overwrite_with(src, dst),
+            (Pending::Move { src, dst }, 'r') => self.start_move(src, fileops::unique_name(&dst), false),
+            (Pending::BulkRename { renames, steps }, 'y') => self.bulk_rename(renames.len(), steps),
+            (Pending::Move { src, .. }, 's') => {
+                self.shell.feedback = Some(Feedback::Info(format!("Skipped {}", src.display())));
+                Ok(())
+            },
+            (_, 'n') => Ok(()),
+            (pending, _) => {
+                self.prompt = Some(Prompt { question: prompt.question, pending });
+                return;
+            }
+        };
+        if let Err(e) = outcome {
+            self.shell.feedback = Some(Feedback::Error(e.to_string()));
+        }
+    }
+
+    pub fn cancel_prompt(&mut self) {
+        self.prompt = None;
+    }
+
+    /// Collects the outcome of the background job once it is done.
+    pub fn tick(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.collect();
+        }
+        if let Some(finder) = &mut self.finder {
+            finder.collect();
+        }
+        let finished = match &mut self.job {
+            Some(job) => job.poll(),
+            None => return,
+        };
+        if let Some(result) = finished {
+            self.job = None;
+            self.register.settle(result.is_ok());
+            self.reload();
+            self.show_read_tree();
+            self.shell.feedback = Some(match result {
+                Ok(done) => Feedback::Info(done),
+                Err(e) => Feedback::Error(e),
+            });
+        }
+    }
+
+    pub fn cancel_job(&mut self) {
+        if let Some(job) = &self.job {
+            job.cancel();
+        }
+    }
+
+    fn mov(&mut self, target: &Path, destination: &Path) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let src: PathBuf = self.resolve(target);
+        if src.symlink_metadata().is_err() {
+            return Err(ShellError::InvalidArgument {
+                argument: target.display().to_string(),
+                reason: "does not exist".to_string()
+            });
+        }
+        let name = src.file_name().ok_or(ShellError::Failed(format!("Cannot move {}", src.display())))?;
+        let dst: PathBuf = match self.resolve(destination) {
+            dir if dir.is_dir() => dir.join(name),
+            file => file,
+        };
+        if dst == src {
+            return Ok(());
+        }
+        if dst.starts_with(&src) {
+            return Err(ShellError::Failed(format!("Cannot move {} into itself", src.display())));
+        }
+        if dst.symlink_metadata().is_ok() {
+            self.prompt = Some(Prompt {
+                question: format!("{} already exists, [o]verwrite, [s]kip or [r]ename?", dst.display()),
+                pending: Pending::Move { src, dst },
+            });
+            return Ok(());
+        }
+        self.start_move(src, dst, false)
+    }
+
+    /// Moves the marked entries into `destination`, or the selected one like `mov` would.
+    fn mov_marked_or_selected(&mut self, destination: &Path) -> ShellResult<()> {
+        let targets: Vec<PathBuf> = self.marked_or_selected("Nothing to move")?;
+        if let [target] = targets.as_slice() {
+            let target = target.to_owned();
+            return self.mov(&target, destination).inspect(|_| self.marked.clear());
+        }
+        let into: PathBuf = self.resolve(destination);
+        if !into.is_dir() {
+            return Err(ShellError::InvalidArgument {
+                argument: destination.display().to_string(),
+                reason: "is not a folder to move the marked entries into".to_string()
+            });
+        }
+        self.transfer(targets, into, true)?;
+        self.marked.clear();
+        Ok(())
+    }
+
+    fn overwrite_with(&mut self, src: PathBuf, dst: PathBuf) -> ShellResult<()> {
+        if src.starts_with(&dst) {
+            return Err(ShellError::Failed(format!("Cannot overwrite {} with its own contents", dst.display())));
+        }
+        self.start_move(src, dst, true)
+    }
+
+    /// Renames right away when possible, moves across devices in the background.
+    /// When `replace` is set `src` lands next to `dst` first and only then takes its place,
+    /// so a failed or cancelled move leaves `dst` as it was.
+    fn start_move(&mut self, src: PathBuf, dst: PathBuf, replace: bool) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let moved = format!("Moved {} to {}", src.display(), dst.display());
+        let landing: PathBuf = match replace {
+            true => fileops::aside_name(&dst, "incoming"),
+            false => dst.to_owned(),
+        };
+        match fs::rename(&src, &landing) {
+            Ok(_) => {
+                let swapped = match replace {
+                    true => fileops::swap_in(&landing, &dst),
+                    false => Ok(()),
+                };
+                self.reload();
+                swapped.map_err(|e| ShellError::Failed(format!("{}: {e}", dst.display())))?;
+                self.shell.feedback = Some(Feedback::Info(moved));
+                Ok(())
+            },
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                let name = src.file_name().unwrap_or(src.as_os_str()).to_string_lossy().to_string();
+                self.job = Some(Job::spawn(format!("Moving {name}"), move |reporter| {
+                    let total = fileops::tree_size(&src, &|| !reporter.cancelled()).map_err(|e| format!("{}: {e}", src.display()))?;
+                    let mut done = 0;
+                    fileops::move_across(&src, &landing, &mut |copied| {
+                        if reporter.cancelled() {
+                            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                        }
+                        done += copied;
+                        reporter.progress(done, total);
+                        Ok(())
+                    }).map_err(|e| format!("{}: {e}", src.display()))?;
+                    if replace {
+                        fileops::swap_in(&landing, &dst).map_err(|e| format!("{}: {e}", dst.display()))?;
+                    }
+                    Ok(moved)
+                }));
+                Ok(())
+            },
+            Err(e) => Err(ShellError::Failed(format!("{}: {e}", src.display()))),
+        }
+    }
+
+    /// The resolved `targets`, or the selected node when none were given.
+    fn targets_or_selected(&self, targets: &[PathBuf]) -> ShellResult<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for target in targets {
+            let path: PathBuf = self.resolve(target);
+            if path.symlink_metadata().is_err() {
+                return Err(ShellError::InvalidArgument {
+                    argument: target.display().to_string(),
+                    reason: "does not exist".to_string()
+                });
+            }
+            paths.push(path);
+        }
+        if paths.is_empty() {
+            paths = self.marked_or_selected("Nothing to copy")?;
+        }
+        Ok(paths)
+    }
+
+    /// The marked entries, or the selected one when nothing is marked.
+    /// Entries inside a marked folder are left out, they go along with it.
+    fn marked_or_selected(&self, nothing: &str) -> ShellResult<Vec<PathBuf>> {
+        if !self.marked.is_empty() {
+            // Sorted by component, a folder comes right before whatever is inside it.
+            let mut outermost: Vec<PathBuf> = Vec::with_capacity(self.marked.len());
+            for path in &self.marked {
+                if !outermost.last().is_some_and(|folder| path.starts_with(folder)) {
+                    outermost.push(path.to_owned());
+                }
+            }
+            return Ok(outermost);
+        }
+        self.selected()
+            .map(|node| vec![node.root_path.to_path_buf()])
+            .ok_or(ShellError::Failed(nothing.to_string()))
+    }
+
+    fn cpy(&mut self, targets: &[PathBuf], cut: bool) -> ShellResult<()> {
+        let paths: Vec<PathBuf> = self.targets_or_selected(targets)?;
+        self.yank(paths, cut);
+        self.marked.clear();
+        Ok(())
+    }
+
+    /// Places the path, name or text contents of the targets on the system clipboard.
+    fn clip(&mut self, clip: Clip, targets: &[PathBuf]) -> ShellResult<()> {
+        let paths: Vec<PathBuf> = self.targets_or_selected(targets)?;
+        let name = |path: &PathBuf| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string();
+        let (text, copied): (String, String) = match (clip, paths.as_slice()) {
+            (Clip::Path, [path]) => (path.display().to_string(), path.display().to_string()),
+            (Clip::Path, _) => (paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join("\n"),
+                                format!("{} paths", paths.len())),
+            (Clip::Name, [path]) => (name(path), name(path)),
+            (Clip::Name, _) => (paths.iter().map(name).collect::<Vec<_>>().join("\n"),
+                                format!("{} names", paths.len())),
+            (Clip::Contents, [path]) => (Self::clip_contents(path)?, format!("the contents of {}", name(path))),
+            (Clip::Contents, _) => return Err(ShellError::Failed("--contents takes a single file".to_string())),
+        };
+        // The terminal cannot tell whether it understood OSC 52, so the desktop clipboard is set too.
+        let terminal = clipboard::osc52(&text);
+        let desktop = self.platform.clipboard(&text);
+        if let (Err(_), Err(e)) = (terminal, desktop) {
+            return Err(ShellError::Failed(format!("Clipboard: {e}")));
+        }
+        self.shell.feedback = Some(Feedback::Info(format!("Copied {copied} to the clipboard")));
+        self.marked.clear();
+        Ok(())
+    }
+
+    fn clip_contents(path: &Path) -> ShellResult<String> {
+        let failed = |reason: &str| ShellError::Failed(format!("{} {reason}", path.display()));
+        let md = fs::metadata(path).map_err(|e| failed(&format!("cannot be read, {e}")))?;
+        if md.is_dir() {
+            return Err(failed("is a folder"));
+        }
+        if md.len() > CLIPBOARD_MAX_BYTES {
+            return Err(failed("is too large for the clipboard"));
+        }
+        let bytes: Vec<u8> = fs::read(path).map_err(|e| failed(&format!("cannot be read, {e}")))?;
+        match preview::decode(&bytes) {
+            preview::Decoded::Text(_, text) => Ok(text),
+            preview::Decoded::Binary => Err(failed("is not a text file")),
+        }
+    }
+
+    fn yank(&mut self, paths: Vec<PathBuf>, cut: bool) {
+        let action = if cut { "Cut" } else { "Yanked" };
+        self.shell.feedback = Some(Feedback::Info(match paths.as_slice() {
+            [path] => format!("{action} {}, press p to paste it", path.display()),
+            _ => format!("{action} {} items, press p to paste them", paths.len()),
+        }));
+        self.register = AppRegister { paths, cut, moving: false };
+    }
+
+    /// Yanks the marked or selected nodes, or cuts them when `cut` is set.
+    pub fn yank_selected(&mut self, cut: bool) {
+        if let Ok(paths) = self.marked_or_selected("Nothing to yank") {
+            self.yank(paths, cut);
+            self.marked.clear();
+        }
+    }
+
+    /// Copies, or moves when cut, the register into the cwd in the background.
+    pub fn paste(&mut self) {
+        if self.register.paths.is_empty() {
+            self.shell.feedback = Some(Feedback::Error("Nothing to paste, yank something with y first".to_string()));
+            return;
+        }
+        let cut = self.register.cut;
+        let paths: Vec<PathBuf> = self.register.paths.clone();
+        let cwd: PathBuf = self.cwd.path().to_path_buf();
+        match self.transfer(paths, cwd, cut) {
+            Ok(_) => self.register.moving = cut,
+            Err(e) => self.shell.feedback = Some(Feedback::Error(e.to_string())),
+        }
+    }
+
+    /// Copies, or moves when `cut`, `paths` into the folder `into` in the background.
+    /// Existing names are never overwritten, the copy gets a free name instead.
+    fn transfer(&mut self, paths: Vec<PathBuf>, into: PathBuf, cut: bool) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        if let Some(src) = paths.iter().find(|src| into.starts_with(src)) {
+            return Err(ShellError::Failed(format!("Cannot paste {} into itself", src.display())));
+        }
+        let label = match (cut, paths.len()) {
+            (true, 1) => "Moving 1 item".to_string(),
+            (true, n) => format!("Moving {n} items"),
+            (false, 1) => "Pasting 1 item".to_string(),
+            (false, n) => format!("Pasting {n} items"),
+        };
+
+        self.job = Some(Job::spawn(label, move |reporter| {
+            // Cut paths are usually renamed, their size only matters when they cross devices.
+            let total: Cell<u64> = Cell::new(0);
+            if !cut {
+                for src in &paths {
+                    total.set(total.get() + fileops::tree_size(src, &|| !reporter.cancelled()).map_err(|e| format!("{}: {e}", src.display()))?);
+                }
+            }
+            let mut done: u64 = 0;
+            let mut on_copied = |copied: u64| {
+                if reporter.cancelled() {
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                }
+                done += copied;
+                reporter.progress(done, total.get());
+                Ok(())
+            };
+            let mut pasted: usize = 0;
+            for src in &paths {
+                if cut && src.parent() == Some(into.as_path()) {
+                    continue;
+                }
+                let name = src.file_name().ok_or(format!("Cannot paste {}", src.display()))?;
+                let dst: PathBuf = match into.join(name) {
+                    taken if taken.symlink_metadata().is_ok() => fileops::unique_name(&taken),
+                    free => free,
+                };
+                let pasted_one = match cut {
+                    true => match fs::rename(src, &dst) {
+                        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                            fileops::tree_size(src, &|| !reporter.cancelled())
+                                .inspect(|size| total.set(total.get() + size))
+                                .and_then(|_| fileops::move_across(src, &dst, &mut on_copied))
+                        },
+                        other => other,
+                    },
+                    false => fileops::copy_tree_with(src, &dst, &mut on_copied).inspect_err(|_| {
+                        let _ = fileops::remove_tree(&dst);
+                    }),
+                };
+                pasted_one.map_err(|e| format!("{}: {e}", src.display()))?;
+                pasted += 1;
+            }
+            Ok(match (cut, pasted) {
+                (true, 1) => "Moved 1 item".to_string(),
+                (true, n) => format!("Moved {n} items"),
+                (false, 1) => "Pasted 1 item".to_string(),
+                (false, n) => format!("Pasted {n} items"),
+            })
+        }));
+        Ok(())
+    }
+
+    /// Compresses the marked or selected entries into `archive` in the background,
+    /// by default a free name in the cwd after the entry, or after the cwd for several.
+    fn zip(&mut self, archive: Option<PathBuf>) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let level: u32 = self.config.archive.level;
+        if level > archive::MAX_LEVEL {
+            return Err(ShellError::Failed(format!("The archive level in cnf.toml goes from 0 to {}", archive::MAX_LEVEL)));
+        }
+        let paths: Vec<PathBuf> = self.marked_or_selected("Nothing to compress")?;
+        let archive: PathBuf = match archive {
+            Some(archive) => {
+                let mut archive: PathBuf = self.resolve(&archive);
+                if !archive.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")) {
+                    archive.as_mut_os_string().push(".zip");
+                }
+                if archive.symlink_metadata().is_ok() {
+                    return Err(ShellError::InvalidArgument {
+                        argument: archive.display().to_string(),
+                        reason: "already exists".to_string()
+                    });
+                }
+                archive
+            },
+            None => {
+                let named_after: &Path = match paths.as_slice() {
+                    [path] => path,
+                    _ => self.cwd.path(),
+                };
+                let stem: String = named_after.file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or("archive".to_string());
+                match self.cwd.path().join(format!("{stem}.zip")) {
+                    taken if taken.symlink_metadata().is_ok() => fileops::unique_name(&taken),
+                    free => free,
+                }
+            },
+        };
+        if !archive.parent().is_some_and(Path::is_dir) {
+            return Err(ShellError::InvalidArgument {
+                argument: archive.display().to_string(),
+                reason: "is not inside an existing folder".to_string()
+            });
+        }
+        let name: String = archive.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        self.job = Some(Job::spawn(format!("Compressing into {name}"), move |reporter| {
+            let total: u64 = paths.iter()
+                .map(|src| fileops::tree_size(src, &|| !reporter.cancelled()).map_err(|e| format!("{}: {e}", src.display())))
+                .sum::<Result<u64, String>>()?;
+            let mut done: u64 = 0;
+            let mut on_written = |written: u64| {
+                if reporter.cancelled() {
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                }
+                done += written;
+                reporter.progress(done.min(total), total);
+                Ok(())
+            };
+            let files: usize = archive::compress(&paths, &archive, level, &mut on_written)
+                .map_err(|e| format!("{}: {e}", archive.display()))?;
+            Ok(match files {
+                1 => format!("Compressed 1 file into {name}"),
+                n => format!("Compressed {n} files into {name}"),
+            })
+        }));
+        self.marked.clear();
+        Ok(())
+    }
+
+    /// Extracts `archive`, or the selected one, in the background into `destination`,
+    /// by default a free folder in the cwd named after the archive.
+    fn unz(&mut self, archive: Option<PathBuf>, destination: Option<PathBuf>) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let archive: PathBuf = match archive {
+            Some(archive) => self.resolve(&archive),
+            None => self.selected()
+                .map(|node| node.root_path.to_path_buf())
+                .ok_or(ShellError::Failed("Nothing to extract".to_string()))?,
+        };
+        if !archive.is_file() {
+            return Err(ShellError::InvalidArgument {
+                argument: archive.display().to_string(),
+                reason: "is not a zip archive".to_string()
+            });
+        }
+        let into: PathBuf = match destination {
+            Some(destination) => self.resolve(&destination),
+            None => {
+                let stem = archive.file_stem().unwrap_or(archive.as_os_str());
+                match self.cwd.path().join(stem) {
+                    taken if taken.symlink_metadata().is_ok() => fileops::unique_name(&taken),
+                    free => free,
+                }
+            },
+        };
+        if into.symlink_metadata().is_ok() && !into.is_dir() {
+            return Err(ShellError::InvalidArgument {
+                argument: into.display().to_string(),
+                reason: "is not a folder to extract into".to_string()
+            });
+        }
+        let name: String = archive.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        self.job = Some(Job::spawn(format!("Extracting {name}"), move |reporter| {
+            let total: u64 = archive::extracted_size(&archive).map_err(|e| format!("{name}: {e}"))?;
+            let mut done: u64 = 0;
+            let mut on_written = |written: u64| {
+                if reporter.cancelled() {
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+                }
+                done += written;
+                reporter.progress(done.min(total), total);
+                Ok(())
+            };
+            let files: usize = archive::extract(&archive, &into, &mut on_written).map_err(|e| format!("{name}: {e}"))?;
+            Ok(match files {
+                1 => format!("Extracted 1 file into {}", into.display()),
+                n => format!("Extracted {n} files into {}", into.display()),
+            })
+        }));
+        Ok(())
+    }
+
+    /// Searches below the cwd in the background, results show up as they are found.
+    /// The index answers right away when it covers the cwd, unless `walk` asks for the disk.
+    fn ser(&mut self, pattern: String, regex: bool, walk: bool) -> ShellResult<()> {
+        let matcher: Pattern = Pattern::parse(&pattern, regex)
+            .map_err(|reason| ShellError::InvalidArgument { argument: pattern.to_owned(), reason })?;
+        let root: PathBuf = self.cwd.path().to_path_buf();
+        let (sender, found) = mpsc::channel();
+        if !walk {
+            let indexed: Option<Vec<PathBuf>> = match self.index.try_lock().as_deref() {
+                Ok(IndexState::Ready(index)) if index.covers(&root) => Some(index.search(&root, &matcher)),
+                _ => None,
+            };
+            if let Some(results) = indexed {
+                self.shell.feedback = Some(Feedback::Info(
+                    format!("Found {} matches in the index, ser --walk searches the disk instead", results.len())
+                ));
+                results.into_iter().for_each(|path| { let _ = sender.send(Found { path, line: None }); });
+                self.search = Some(AppSearch::new(pattern, root, false, found));
+                self.mode = Mode::InsideSearch;
+                return Ok(());
+            }
+        }
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let walked: PathBuf = root.clone();
+        let label: String = format!("Searching for {pattern}");
+
+        self.job = Some(Job::spawn(label, move |reporter| {
+            let (scanned, matches) = (AtomicU64::new(0), AtomicU64::new(0));
+            let searched = WalkOptions { gitignore: true, max_depth: None };
+            internal::walk(&walked, searched, &|entry| {
+                let seen = scanned.fetch_add(1, Ordering::Relaxed) + 1;
+                if matcher.matches(&entry.file_name().to_string_lossy()) {
+                    matches.fetch_add(1, Ordering::Relaxed);
+                    let _ = sender.send(Found { path: entry.path(), line: None });
+                }
+                if seen % 1000 == 0 {
+                    reporter.progress(seen, 0);
+                }
+                !reporter.cancelled()
+            });
+            let (scanned, matches) = (scanned.into_inner(), matches.into_inner());
+            Ok(match reporter.cancelled() {
+                true => format!("Search cancelled, {matches} matches in {scanned} entries"),
+                false => format!("Found {matches} matches in {scanned} entries"),
+            })
+        }));
+        self.search = Some(AppSearch::new(pattern, root, false, found));
+        self.mode = Mode::InsideSearch;
+        Ok(())
+    }
+
+    /// Searches the contents of the text files below the cwd in the background.
+    fn grp(&mut self, pattern: String, regex: bool) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let source: String = match regex {
+            true => pattern.to_owned(),
+            false => regex::escape(&pattern),
+        };
+        let matcher = search::compile_regex(&source)
+            .map_err(|reason| ShellError::InvalidArgument { argument: pattern.to_owned(), reason })?;
+        let root: PathBuf = self.cwd.path().to_path_buf();
+        let (sender, found) = mpsc::channel();
+        let walked: PathBuf = root.clone();
+        let label: String = format!("Searching contents for {pattern}");
+
+        self.job = Some(Job::spawn(label, move |reporter| {
+            let (files, matches) = (AtomicU64::new(0), AtomicU64::new(0));
+            let searched = WalkOptions { gitignore: true, max_depth: None };
+            internal::walk(&walked, searched, &|entry| {
+                let readable = entry.metadata().ok().filter(|md| md.is_file() && md.len() <= GREP_MAX_BYTES);
+                let text: Option<String> = readable
+                    .and_then(|_| fs::read(entry.path()).ok())
+                    .and_then(|bytes| match preview::decode(&bytes) {
+                        preview::Decoded::Text(_, text) => Some(text),
+                        preview::Decoded::Binary => None,
+                    });
+                if let Some(text) = text {
+                    for line in search::grep(&text, &matcher) {
+                        matches.fetch_add(1, Ordering::Relaxed);
+                        let _ = sender.send(Found { path: entry.path(), line: Some(line) });
+                    }
+                    let searched = files.fetch_add(1, Ordering::Relaxed) + 1;
+                    if searched % 100 == 0 {
+                        reporter.progress(searched, 0);
+                    }
+                }
+                !reporter.cancelled() && matches.load(Ordering::Relaxed) < GREP_MAX_MATCHES
+            });
+            let (files, matches) = (files.into_inner(), matches.into_inner());
+            Ok(match (reporter.cancelled(), matches >= GREP_MAX_MATCHES) {
+                (true, _) => format!("Search cancelled, {matches} matching lines in {files} files"),
+                (false, true) => format!("Stopped at the first {matches} matching lines"),
+                (false, false) => format!("Found {matches} matching lines in {files} files"),
+            })
+        }));
+        self.search = Some(AppSearch::new(pattern, root, true, found));
+        self.mode = Mode::InsideSearch;
+        Ok(())
+    }
+
+    /// The lines around the selected `grp` match and the number of the first one.
+    pub fn match_context(&mut self) -> Option<(usize, &[String])> {
+        let search: &mut AppSearch = self.search.as_mut()?;
+        let idx: usize = search.selection_idx?;
+        if search.context.as_ref().map(|(read_for, ..)| *read_for) != Some(idx) {
+            let found: &Found = search.results.get(idx)?;
+            let first: usize = found.line.as_ref()?.number.saturating_sub(CONTEXT_BEFORE).max(1);
+            let lines: Vec<String> = preview::text_window(&found.path, first - 1, CONTEXT_LINES).unwrap_or_default();
+            search.context = Some((idx, first, lines));
+        }
+        search.context.as_ref().map(|(_, first, lines)| (*first, lines.as_slice()))
+    }
+
+    fn inspect_index(&mut self) -> ShellResult<()> {
+        let state = self.index.lock().map_err(|_| ShellError::Failed("The index is unavailable".to_string()))?;
+        let inspected: String = match &*state {
+            IndexState::Missing => "There is no index yet, idx --build [path] indexes a folder".to_string(),
+            IndexState::Refreshing => "The index is catching up with the disk".to_string(),
+            IndexState::Ready(index) => {
+                let (names, folders) = index.counts();
+                format!("{names} names in {folders} folders indexed below {}, refreshed {}",
+                        index.root.display(), index.refreshed.format("%d/%m/%Y %H:%M"))
+            },
+        };
+        drop(state);
+        self.shell.feedback = Some(Feedback::Info(inspected));
+        Ok(())
+    }
+
+    /// Indexes `target`, or the cwd, in the background and saves it for the next runs.
+    fn build_index(&mut self, target: Option<&Path>) -> ShellResult<()> {
+        if let Some(job) = &self.job {
+            return Err(ShellError::Failed(format!("Wait for {} to finish", job.label)));
+        }
+        let root: PathBuf = match target {
+            Some(target) => self.resolve(target),
+            None => self.cwd.path().to_path_buf(),
+        };
+        if !root.is_dir() {
+            return Err(ShellError::InvalidArgument {
+                argument: root.display().to_string(),
+                reason: "is not a directory".to_string()
+            });
+        }
+        let location: PathBuf = index::location(&self.home);
+        let state: Arc<Mutex<IndexState>> = Arc::clone(&self.index);
+        let label: String = format!("Indexing {}", root.display());
+
+        self.job = Some(Job::spawn(label, move |reporter| {
+            let index = Index::build(root, &|count| {
+                if count % 1000 == 0 {
+                    reporter.progress(count, 0);
+                }
+                !reporter.cancelled()
+            }).map_err(|e| e.to_string())?;
+            if reporter.cancelled() {
+                return Err("Indexing cancelled, the previous index is kept".to_string());
+            }
+            index.save(&location).map_err(|e| format!("{}: {e}", location.display()))?;
+            let (names, _) = index.counts();
+            let built = format!("Indexed {names} names below {}", index.root.display());
+            *state.lock().map_err(|_| "The index is unavailable".to_string())? = IndexState::Ready(index);
+            Ok(built)
+        }));
+        Ok(())
+    }
+
+    /// Goes back to the results of the last search.
+    pub fn open_search(&mut self) {
+        match self.search {
+            Some(_) => self.mode = Mode::InsideSearch,
+            None => self.shell.feedback = Some(Feedback::Error("Nothing was searched yet".to_string())),
+        }
+    }
+
+    /// Back to normal mode, stopping the search if it is still running.
+    pub fn close_search(&mut self) {
+        if self.search.as_ref().is_some_and(|search| !search.finished) {
+            self.cancel_job();
+        }
+        self.mode = Mode::Normal;
+    }
+
+    pub fn shift_search(&mut self, down: bool) {
+        if let Some(search) = &mut self.search {
+            search.shift(down);
+        }
+    }
+
+    /// Moves to the folder of the selected result, with the result selected.
+    pub fn jump_to_result(&mut self) {
+        if let Some(found) = self.search.as_ref().and_then(|s| s.selected()) {
+            self.reveal(found.path.to_owned());
+        }
+    }
+
+    /// Moves to the folder of `found` with it selected, back in normal mode.
+    fn reveal(&mut self, found: PathBuf) {
+        let parent: PathBuf = found.parent().unwrap_or(&found).to_path_buf();
+        match self.change_dir(parent) {
+            Ok(_) => {
+                self.selection_idx = self.cwd.position(&found).or(self.selection_idx);
+                self.preview = None;
+                self.mode = Mode::Normal;
+            },
+            Err(e) => self.shell.feedback = Some(Feedback::Error(format!("{}: {e}", found.display()))),
+        }
+    }
+
+    /// Reads the selected text file whole and shows it full screen.
+    pub fn open_viewer(&mut self) {
+        let path: PathBuf = match self.selected() {
+            Some(node) if node.is_file => node.root_path.to_path_buf(),
+            _ => return,
+        };
+        if let Err(e) = self.view(path) {
+            self.shell.feedback = Some(Feedback::Error(e));
+        }
+    }
+
+    /// Reads the text file at `path` whole into the viewer.
+    fn view(&mut self, path: PathBuf) -> Result<(), String> {
+        let size: u64 = fs::metadata(&path).map_err(|e| format!("{}: {e}", path.display()))?.len();
+        if size > VIEWER_MAX_BYTES {
+            return Err(format!("{} is too large to view", path.display()));
+        }
+        let (encoding, text) = match fs::read(&path).map(|bytes| preview::decode(&bytes)) {
+            Ok(preview::Decoded::Text(encoding, text)) => (encoding, text),
+            Ok(preview::Decoded::Binary) => return Err(format!("{} is not a text file, x shows it as hex", path.display())),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        let lines: Vec<String> = text.lines().map(|l| l.replace('\t', "    ")).collect();
+        let extension: String = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default();
+        // Same rule as the preview, large files are usually generated.
+        let highlights = Syntax::from_extension(&extension)
+            .filter(|_| size <= self.config.preview.highlight_max_size)
+            .map(|syntax| highlight::highlight(syntax, &lines));
+        self.viewer = Some(AppViewer::new(path, encoding, lines, highlights));
+        self.mode = Mode::InsideViewer;
+        Ok(())
+    }
+
+    /// Leaves the viewer, the cwd and selection are where they were.
+    pub fn close_viewer(&mut self) {
+        self.viewer = None;
+        self.mode = Mode::Normal;
+    }
+
+    pub fn scroll_viewer(&mut self, lines: i64) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.scroll(lines);
+        }
+    }
+
+    pub fn scroll_viewer_page(&mut self, down: bool) {
+        if let Some(viewer) = &mut self.viewer {
+            let page = viewer.page as i64;
+            viewer.scroll(if down { page } else { -page });
+        }
+    }
+
+    pub fn viewer_edge(&mut self, end: bool) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.scroll = if end { viewer.lines.len().saturating_sub(viewer.page) } else { 0 };
+        }
+    }
+
+    pub fn side_scroll_viewer(&mut self, right: bool) {
+        if let Some(viewer) = self.viewer.as_mut().filter(|v| !v.wrap) {
+            viewer.side_scroll(right);
+        }
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.wrap = !viewer.wrap;
+            viewer.side = 0;
+        }
+    }
+
+    pub fn viewer_search(&mut self) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.typing = Some(String::new());
+        }
+    }
+
+    pub fn viewer_typing(&self) -> bool {
+        self.viewer.as_ref().is_some_and(|v| v.typing.is_some())
+    }
+
+    pub fn viewer_type(&mut self, c: char) {
+        if let Some(typing) = self.viewer.as_mut().and_then(|v| v.typing.as_mut()) {
+            typing.push(c);
+        }
+    }
+
+    pub fn viewer_erase(&mut self) {
+        if let Some(typing) = self.viewer.as_mut().and_then(|v| v.typing.as_mut()) {
+            typing.pop();
+        }
+    }
+
+    pub fn viewer_find(&mut self) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.search();
+        }
+    }
+
+    pub fn viewer_cancel_search(&mut self) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.typing = None;
+        }
+    }
+
+    pub fn viewer_next_match(&mut self, forward: bool) {
+        if let Some(viewer) = &mut self.viewer {
+            viewer.next_match(forward);
+        }
+    }
+
+    /// Lists the files below the cwd in the background and opens the fuzzy finder over them.
+    pub fn open_finder(&mut self) {
+        let root: PathBuf = self.cwd.path().to_path_buf();
+        let (sender, found) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        let (walked, stop) = (root.clone(), Arc::clone(&closed));
+        thread::spawn(move || {
+            let listed = AtomicU64::new(0);
+            let listing = WalkOptions { gitignore: true, max_depth: None };
+            internal::walk(&walked, listing, &|entry| {
+                if entry.file_type().is_ok_and(|t| !t.is_dir()) {
+                    let path: PathBuf = entry.path();
+                    let relative: &Path = path.strip_prefix(&walked).unwrap_or(&path);
+                    let _ = sender.send(relative.to_string_lossy().into_owned());
+                    listed.fetch_add(1, Ordering::Relaxed);
+                }
+                !stop.load(Ordering::Relaxed) && listed.load(Ordering::Relaxed) < FINDER_MAX_FILES as u64
+            });
+        });
+        // The previous finder goes away here, which stops its listing.
+        self.finder = Some(AppFinder::new(root, found, closed));
+        self.mode = Mode::InsideFinder;
+    }
+
+    pub fn close_finder(&mut self) {
+        self.finder = None;
+        self.mode = Mode::Normal;
+    }
+
+    pub fn finder_type(&mut self, c: char) {
+        if let Some(finder) = &mut self.finder {
+            finder.push(c);
+        }
+    }
+
+    pub fn finder_erase(&mut self) {
+        if let Some(finder) = &mut self.finder {
+            finder.pop();
+        }
+    }
+
+    pub fn shift_finder(&mut self, down: bool) {
+        if let Some(finder) = &mut self.finder {
+            finder.shift(down);
+        }
+    }
+
+    /// Moves to the folder of the chosen file, with the file selected.
+    pub fn choose_found(&mut self) {
+        let chosen: Option<PathBuf> = self.finder.as_ref().and_then(|f| f.selected());
+        if let Some(found) = chosen {
+            self.finder = None;
+            self.reveal(found);
+        }
+    }
+
+    pub fn open_trash(&mut self) {
+        match self.platform.trashed() {
+            Ok(items) => {
+                self.trash.selection_idx = if items.is_empty() { None } else { Some(0) };
+                self.trash.items = items;
+                self.mode = Mode::InsideTrash;
+            },
+            Err(e) => self.shell.feedback = Some(Feedback::Error(format!("Trash: {e}"))),
+        }
+    }
+
+    pub fn close_trash(&mut self) {
+        self.mode = Mode::Normal;
+        self.reload();
+    }
+
+    fn reload_trash(&mut self) -> ShellResult<()> {
+        let items = self.platform.trashed().map_err(|e| ShellError::Failed(format!("Trash: {e}")))?;
+        self.trash.selection_idx = match items.is_empty() {
+            true => None,
+            false => Some(self.trash.selection_idx.unwrap_or(0).min(items.len() - 1)),
+        };
+        self.trash.items = items;
+        Ok(())
+    }
+
+    fn selected_trash(&self) -> Option<&TrashItem> {
+        self.trash.selection_idx.and_then(|idx| self.trash.items.get(idx))
+    }
+
+    pub fn shift_trash(&mut self, down: bool) {
+        let len = self.trash.items.len();
+        self.trash.selection_idx = match (self.trash.selection_idx, down) {
+            _ if len == 0 => None,
+            (Some(k), true) if k + 1 < len => Some(k + 1),
+            (Some(_), true) => Some(0),
+            (Some(k), false) if k > 0 => Some(k - 1),
+            (Some(_), false) => Some(len - 1),
+            (None, _) => Some(0),
+        };
+    }
+
+    pub fn restore_trashed(&mut self) {
+        let item: TrashItem = match self.selected_trash() {
+            Some(item) => item.to_owned(),
+            None => return,
+        };
+        let restored = self.platform.restore(&item)
+            .map_err(|e| ShellError::Failed(format!("{}: {e}", item.name)))
+            .and_then(|_| self.reload_trash());
+        self.shell.feedback = Some(match restored {
+            Ok(_) => Feedback::Info(format!("Restored {}", item.original.display())),
+            Err(e) => Feedback::Error(e.to_string()),
+        });
+    }
+
+    pub fn ask_purge(&mut self) {
+        if let Some(item) = self.selected_trash() {
+            self.prompt = Some(Prompt {
+                question: format!("Delete {} for good? [y/n]", item.name),
+                pending: Pending::Purge(item.to_owned()),
+            });
+        }
+    }
+
+    pub fn ask_empty_trash(&mut self) {
+        if !self.trash.items.is_empty() {
+            self.prompt = Some(Prompt {
+                question: format!("Delete all {} items in the trash for good? [y/n]", self.trash.items.len()),
+                pending: Pending::EmptyTrash,
+            });
+        }
+    }
+
+    fn purge(&mut self, item: &TrashItem) -> ShellResult<()> {
+        self.platform.purge(item).map_err(|e| ShellError::Failed(format!("{}: {e}", item.name)))?;
+        self.shell.feedback = Some(Feedback::Info(format!("Deleted {}", item.name)));
+        self.reload_trash()
+    }
+
+    fn empty_trash(&mut self) -> ShellResult<()> {
+        self.platform.empty_trash().map_err(|e| ShellError::Failed(format!("Trash: {e}")))?;
+        self.shell.feedback = Some(Feedback::Info("Emptied the trash".to_string()));
+        self.reload_trash()
+    }
+
+    /// Where a new file or folder would go, as long as nothing is there yet.
+    fn creatable(&self, target: &Path) -> ShellResult<PathBuf> {
+        let path: PathBuf = self.resolve(target);
+        match path.symlink_metadata() {
+            Ok(_) => Err(ShellError::InvalidArgument {
+                argument: target.display().to_string(),
+                reason: "already exists".to_string()
+            }),
+            Err(_) => Ok(path),
+        }
+    }
+
+    /// Creates an empty file, or a copy of a template, along with the folders leading to it.
+    fn new_file(&mut self, target: &Path, template: Option<PathBuf>) -> ShellResult<()> {
+        let path: PathBuf = self.creatable(target)?;
+        let source: Option<PathBuf> = match template {
+            Some(name) => {
+                let templates: PathBuf = config::templates_dir();
+                let source: PathBuf = templates.join(&name);
+                if name.components().count() != 1 || !source.is_file() {
+                    return Err(ShellError::InvalidArgument {
+                        argument: name.display().to_string(),
+                        reason: format!("is not a template in {}", templates.display())
+                    });
+                }
+                Some(source)
+            },
+            None => None,
+        };
+        let created = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| match &source {
+            Some(source) => fs::copy(source, &path).map(|_| ()),
+            None => fs::OpenOptions::new().write(true).create_new(true).open(&path).map(|_| ()),
+        });
+        created.map_err(|e| ShellError::Failed(format!("{}: {e}", path.display())))?;
+        self.select_created(&path);
+        Ok(())
+    }
+
+    /// Creates a folder and any missing folder leading to it.
+    fn mkd(&mut self, target: &Path) -> ShellResult<()> {
+        let path: PathBuf = self.creatable(target)?;
+        fs::create_dir_all(&path).map_err(|e| ShellError::Failed(format!("{}: {e}", path.display())))?;
+        self.select_created(&path);
+        Ok(())
+    }
+
+    /// Selects what was just created, or the entry of the cwd leading to it.
+    fn select_created(&mut self, path: &Path) {
+        self.reload();
+        if let Some(idx) = path.ancestors().find_map(|p| self.cwd.position(p)) {
+            self.selection_idx = Some(idx);
+        }
+        self.shell.feedback = Some(Feedback::Info(format!("Created {}", path.display())));
+    }
+
+    fn scd(&mut self, target: &Path) -> ShellResult<()> {
+        let invalid = |reason: String| ShellError::InvalidArgument {
+            argument: target.display().to_string(),
+            reason
+        };
+        let destination: PathBuf = if target == Path::new("-") {
+            match &self.previous_dir {
+                Some(previous) => previous.to_path_buf(),
+                None => return Err(invalid("has no previous directory".to_string())),
+            }
+        } else {
+            self.resolve(target)
+        };
+
+        match destination.metadata() {
+            Ok(md) if md.is_dir() => {},
+            Ok(_) => return Err(invalid("is not a directory".to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(invalid("does not exist".to_string())),
+            Err(e) => return Err(invalid(format!("is not accessible, {e}"))),
+        }
+        self.change_dir(destination)
+            .map_err(|e| invalid(format!("is not readable, {e}")))
+    }
+    
+    pub fn append_to_shell(&mut self, pressed: char) {
+        self.shell.feedback = None;
+        // A second `:` on an empty line is swallowed, anywhere else it is part of a path or pattern.
+        if pressed == ':' && self.shell.input == ":" {
+            self.reset_shell();
+        } else { 
+            self.shell.append(pressed);
+            self.shell.cursor_shift_right();
+        }
+    }
+
+    pub fn delete_from_shell(&mut self) {
+        self.shell.feedback = None;
+        self.shell.delete();
+    }
+
+    /// Turns the Name cell of the selected row into an editor.
+    pub fn start_rename(&mut self) {
+        let node: &Node = match self.selected() {
+            Some(node) => node,
+            None => return,
+        };
+        let editor = AppShell::editing(&node.name().to_string_lossy());
+        self.rename = Some(AppRename { target: node.root_path.to_path_buf(), editor });
+        self.mode = Mode::InsideRename;
+    }
+
+    pub fn rename_type(&mut self, pressed: char) {
+        if let Some(rename) = &mut self.rename {
+            rename.editor.append(pressed);
+            rename.editor.cursor_shift_right();
+            self.shell.feedback = None;
+        }
+    }
+
+    pub fn rename_erase(&mut self) {
+        if let Some(rename) = &mut self.rename {
+            rename.editor.delete();
+            self.shell.feedback = None;
+        }
+    }
+
+    pub fn rename_cursor(&mut self, direction: CursorDirection) {
+        if let Some(rename) = &mut self.rename {
+            match direction {
+                CursorDirection::Left => rename.editor.cursor_shift_left(),
+                CursorDirection::Right => rename.editor.cursor_shift_right(),
+            }
+        }
+    }
+
+    pub fn cancel_rename(&mut self) {
+        self.rename = None;
+        self.shell.feedback = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// Renames the entry to what was typed, the editor stays open when the name is refused.
+    pub fn finish_rename(&mut self) {
+        let (target, name) = match &self.rename {
+            Some(rename) => (rename.target.to_owned(), rename.editor.input.to_owned()),
+            None => return,
+        };
+        let renamed: PathBuf = target.with_file_name(&name);
+        let refused: Option<String> = match internal::invalid_name(&name) {
+            Some(reason) => Some(reason),
+            None if renamed == target => {
+                self.cancel_rename();
+                return;
+            },
+            // Only the case changes and the file system sees the same name, so nothing is in the way.
+            None if fileops::same_entry(&target, &renamed) => None,
+            None if renamed.symlink_metadata().is_ok() => Some("already exists".to_string()),
+            None => None,
+        };
+        if let Some(reason) = refused {
+            self.shell.feedback = Some(Feedback::Error(format!("'{name}' {reason}")));
+            return;
+        }
+        if let Err(e) = fs::rename(&target, &renamed) {
+            self.shell.feedback = Some(Feedback::Error(format!("{}: {e}", target.display())));
+            return;
+        }
+        self.rename = None;
+        self.mode = Mode::Normal;
+        self.reload();
+        self.selection_idx = self.cwd.position(&renamed).or(self.selection_idx);
+        self.shell.feedback = Some(Feedback::Info(format!("Renamed {} to {name}", target.display())));
+    }
+
+    pub fn reset_shell(&mut self) {
+        self.shell.input = ":".to_string();
+        self.shell.cursor_position = 1;
+    }
+
+    pub fn move_cursor(&mut self, direction: CursorDirection) {
+        match direction {
+            CursorDirection::Left => self.shell.cursor_shift_left(),
+            CursorDirection::Right => self.shell.cursor_shift_right(),
+        }
+    }
+
+    /// Marks the selected entry, or unmarks it, and moves on to the next one.
+    pub fn toggle_mark(&mut self) {
+        if let Some(path) = self.selected().map(|node| node.root_path.to_path_buf()) {
+            if !self.marked.remove(&path) {
+                self.marked.insert(path);
+            }
+            self.shift_down();
+        }
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.marked.clear();
+    }
+
+    /// Starts selecting the rows between here and wherever the selection moves to.
+    pub fn start_visual(&mut self) {
+        if let Some(idx) = self.selection_idx {
+            self.visual_anchor = Some(idx);
+            self.mode = Mode::InsideVisual;
+        }
+    }
+
+    /// Marks every row of the visual range.
+    pub fn finish_visual(&mut self) {
+        let range: Vec<PathBuf> = self.visual_range().into_iter().flatten()
+            .filter_map(|idx| self.cwd.get(idx).map(|node| node.root_path.to_path_buf()))
+            .collect();
+        self.marked.extend(range);
+        self.cancel_visual();
+    }
+
+    pub fn cancel_visual(&mut self) {
+        self.visual_anchor = None;
+        self.mode = Mode::Normal;
+    }
+
+    fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+        self.visual_anchor.zip(self.selection_idx).map(|(anchor, idx)| anchor.min(idx)..=anchor.max(idx))
+    }
+
+    /// Rows of the cwd that are marked or inside the visual range.
+    pub fn marked_rows(&self) -> Vec<usize> {
+        let range = self.visual_range();
+        (0..self.cwd.len()).filter(|idx| {
+            range.as_ref().is_some_and(|range| range.contains(idx))
+                || self.cwd.get(*idx).is_some_and(|node| self.marked.contains(node.root_path.as_ref()))
+        }).collect()
+    }
+
+    pub fn shift_down(&mut self) {
+        if self.cwd.is_empty() {
+            self.selection_idx = None;
+            return;
+        }
+        let i = match self.selection_idx {
+            Some(k) if k + 1 < self.cwd.len() => k + 1,
+            _ => 0
+        };
+        self.selection_idx = Some(i);
+    }
+
+    pub fn shift_up(&mut self) {
+        if self.cwd.is_empty() {
+            self.selection_idx = None;
+            return;
+        }
+        let i = match self.selection_idx {
+            Some(k) if k > 0 => k - 1,
+            Some(_) => self.cwd.len() - 1,
             None => 0
         };
         self.selection_idx =  Some(i)
@@ -0,0 +1,381 @@
+use std::fs;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Timelike};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::FileOptions;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Deflate levels go from 0, only storing files, to 9, the smallest archive.
+pub const MAX_LEVEL: u32 = 9;
+
+/// Copies `reader` into `writer`, calling `on_written` with the size of every chunk.
+/// An error from `on_written` stops the copy.
+fn copy_with(reader: &mut dyn Read, writer: &mut dyn Write, on_written: &mut dyn FnMut(u64) -> io::Result<()>) -> io::Result<()> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        on_written(read as u64)?;
+    }
+}
+
+/// Zip timestamps have no time zone and start in 1980, older files keep the default.
+fn zip_time(md: &fs::Metadata) -> zip::DateTime {
+    let modified: DateTime<Local> = match md.modified() {
+        Ok(modified) => modified.into(),
+        Err(_) => return zip::DateTime::default(),
+    };
+    let in_range = |n: u32| u8::try_from(n).unwrap_or(0);
+    u16::try_from(modified.year()).ok()
+        .and_then(|year| zip::DateTime::from_date_and_time(
+            year,
+            in_range(modified.month()),
+            in_range(modified.day()),
+            in_range(modified.hour()),
+            in_range(modified.minute()),
+            in_range(modified.second()),
+        ).ok())
+        .unwrap_or_default()
+}
+
+fn system_time(time: zip::DateTime) -> Option<SystemTime> {
+    let naive = NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?
+        .and_hms_opt(time.hour().into(), time.minute().into(), time.second().into())?;
+    Local.from_local_datetime(&naive).earliest().map(SystemTime::from)
+}
+
+#[cfg(unix)]
+fn permissions(md: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(md.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn permissions(_md: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_permissions(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))
+}
+
+#[cfg(not(unix))]
+fn set_permissions(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Compresses `paths` and everything inside them into a new `archive`,
+/// calling `on_written` with the size of every chunk read, returns how many files went in.
+/// Entries are named from the folder holding each path, so paths sharing a name are refused.
+/// An error from `on_written` stops it and nothing is left behind.
+pub(crate) fn compress(paths: &[PathBuf], archive: &Path, level: u32, on_written: &mut dyn FnMut(u64) -> io::Result<()>) -> io::Result<usize> {
+    if level > MAX_LEVEL {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the compression level {level} is above {MAX_LEVEL}")));
+    }
+    // Marks from different folders may share a name, the archive could only hold one of them.
+    let mut names: Vec<&OsStr> = paths.iter().filter_map(|path| path.file_name()).collect();
+    names.sort();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' would be in the archive twice", pair[0].to_string_lossy())));
+    }
+    let mut writer = ZipWriter::new(fs::OpenOptions::new().write(true).create_new(true).open(archive)?);
+    let options = match level {
+        0 => FileOptions::default().compression_method(CompressionMethod::Stored),
+        level => FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(level as i32)),
+    };
+    let mut files: usize = 0;
+    let compressed = paths.iter().try_for_each(|path| {
+        let name: String = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        add(&mut writer, path, name, archive, options, &mut files, on_written)
+    }).and_then(|_| writer.finish().map(|_| ()).map_err(io::Error::from));
+
+    if let Err(e) = compressed {
+        let _ = fs::remove_file(archive);
+        return Err(e);
+    }
+    Ok(files)
+}
+
+/// Adds `path` to the archive as `name`, folders with everything inside them.
+/// Symlinks are followed to files, linked folders are left out so loops cannot happen.
+fn add(
+    writer: &mut ZipWriter<fs::File>,
+    path: &Path,
+    name: String,
+    archive: &Path,
+    options: FileOptions,
+    files: &mut usize,
+    on_written: &mut dyn FnMut(u64) -> io::Result<()>,
+) -> io::Result<()> {
+    // An archive written inside a folder being compressed would go in as well.
+    if path == archive {
+        return Ok(());
+    }
+    let linked: bool = fs::symlink_metadata(path)?.file_type().is_symlink();
+    let md = fs::metadata(path)?;
+    let mut options = options.last_modified_time(zip_time(&md));
+    if let Some(mode) = permissions(&md) {
+        options = options.unix_permissions(mode);
+    }
+    if md.is_dir() {
+        if linked {
+            return Ok(());
+        }
+        writer.add_directory(format!("{name}/"), options)?;
+        let mut entries: Vec<fs::DirEntry> = fs::read_dir(path)?.collect::<io::Result<_>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let child: String = format!("{name}/{}", entry.file_name().to_string_lossy());
+            add(writer, &entry.path(), child, archive, options, files, on_written)?;
+        }
+        return Ok(());
+    }
+    writer.start_file(name, options.large_file(md.len() >= u32::MAX as u64))?;
+    copy_with(&mut fs::File::open(path)?, writer, on_written)?;
+    *files += 1;
+    Ok(())
+}
+
+/// Where an entry named `name` lands inside `into`.
+/// Names climbing out with `..`, absolute names and names going through a symlink already
+/// inside `into` are refused, so an archive can never write outside of it.
+fn destination(into: &Path, name: &str, enclosed: Option<&Path>) -> io::Result<PathBuf> {
+    let outside = || io::Error::new(io::ErrorKind::InvalidData, format!("'{name}' would be extracted outside of {}", into.display()));
+    let enclosed: &Path = enclosed.ok_or_else(outside)?;
+    let mut out: PathBuf = into.to_path_buf();
+    for component in enclosed.components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {},
+            _ => return Err(outside()),
+        }
+        if out.symlink_metadata().is_ok_and(|md| md.file_type().is_symlink()) {
+            return Err(outside());
+        }
+    }
+    Ok(out)
+}
+
+/// The uncompressed size of everything in `archive`.
+pub(crate) fn extracted_size(archive: &Path) -> io::Result<u64> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+    let mut size: u64 = 0;
+    for i in 0..zip.len() {
+        size += zip.by_index_raw(i)?.size();
+    }
+    Ok(size)
+}
+
+/// Extracts `archive` into the folder `into`, creating it when missing,
+/// calling `on_written` with the size of every chunk written, returns how many files came out.
+/// Every entry is checked before anything is written, existing files are never overwritten.
+pub(crate) fn extract(archive: &Path, into: &Path, on_written: &mut dyn FnMut(u64) -> io::Result<()>) -> io::Result<usize> {
+    let mut zip = ZipArchive::new(fs::File::open(archive)?)?;
+    let mut planned: Vec<(usize, PathBuf)> = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let entry = zip.by_index_raw(i)?;
+        let out: PathBuf = destination(into, entry.name(), entry.enclosed_name())?;
+        if !entry.is_dir() && out.symlink_metadata().is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", out.display())));
+        }
+        planned.push((i, out));
+    }
+
+    let created: bool = !into.exists();
+    let mut files: usize = 0;
+    let extracted = planned.into_iter().try_for_each(|(i, out)| {
+        let mut entry = zip.by_index(i)?;
+        if entry.is_dir() {
+            return fs::create_dir_all(&out);
+        }
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&out)?;
+        copy_with(&mut entry, &mut file, on_written)?;
+        if let Some(modified) = system_time(entry.last_modified()) {
+            let _ = file.set_modified(modified);
+        }
+        if let Some(mode) = entry.unix_mode() {
+            set_permissions(&out, mode)?;
+        }
+        files += 1;
+        Ok(())
+    });
+
+    if let Err(e) = extracted {
+        // Never leave half an archive behind in a folder made for it.
+        if created {
+            let _ = fs::remove_dir_all(into);
+        }
+        return Err(e);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// An empty folder of its own for every test.
+    fn scratch(test: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!("owl-archive-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// An archive holding `entries` under their names as given, however hostile.
+    fn crafted(path: &Path, entries: &[(&str, &str)]) {
+        let mut writer = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, contents) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn no_progress(_: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = scratch("round-trip");
+        fs::create_dir_all(dir.join("project/src")).unwrap();
+        fs::write(dir.join("project/src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("notes.txt"), "remember").unwrap();
+        let archive = dir.join("both.zip");
+
+        let packed = compress(&[dir.join("project"), dir.join("notes.txt")], &archive, 6, &mut no_progress).unwrap();
+        let unpacked = extract(&archive, &dir.join("out"), &mut no_progress).unwrap();
+
+        assert_eq!((packed, unpacked), (2, 2));
+        assert_eq!(fs::read_to_string(dir.join("out/project/src/main.rs")).unwrap(), "fn main() {}\n");
+        assert_eq!(fs::read_to_string(dir.join("out/notes.txt")).unwrap(), "remember");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stored_when_level_is_zero() {
+        let dir = scratch("stored");
+        fs::write(dir.join("a.txt"), "aaaaaaaaaaaaaaaa").unwrap();
+        let archive = dir.join("a.zip");
+        compress(&[dir.join("a.txt")], &archive, 0, &mut no_progress).unwrap();
+
+        let mut zip = ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+        assert_eq!(zip.by_index(0).unwrap().compression(), CompressionMethod::Stored);
+        assert!(compress(&[dir.join("a.txt")], &dir.join("b.zip"), MAX_LEVEL + 1, &mut no_progress).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_entries_sharing_a_name() {
+        let dir = scratch("same-name");
+        for folder in ["a", "b"] {
+            fs::create_dir(dir.join(folder)).unwrap();
+            fs::write(dir.join(folder).join("readme"), folder).unwrap();
+        }
+        let archive = dir.join("readmes.zip");
+        let packed = compress(&[dir.join("a/readme"), dir.join("b/readme")], &archive, 6, &mut no_progress);
+
+        assert_eq!(packed.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(!archive.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_climbing_out() {
+        let dir = scratch("climbing");
+        let archive = dir.join("evil.zip");
+        crafted(&archive, &[("fine.txt", "ok"), ("../escaped.txt", "pwned")]);
+
+        let unpacked = extract(&archive, &dir.join("out"), &mut no_progress);
+
+        assert_eq!(unpacked.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join("escaped.txt").exists());
+        // Nothing is written before every entry was checked.
+        assert!(!dir.join("out").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_absolute_names() {
+        let dir = scratch("absolute");
+        let archive = dir.join("evil.zip");
+        let target = dir.join("absolute.txt");
+        crafted(&archive, &[(target.to_str().unwrap(), "pwned")]);
+
+        assert!(extract(&archive, &dir.join("out"), &mut no_progress).is_err());
+        assert!(!target.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_going_through_a_symlink() {
+        let dir = scratch("symlink");
+        let (into, outside) = (dir.join("out"), dir.join("outside"));
+        fs::create_dir_all(&into).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, into.join("link")).unwrap();
+        let archive = dir.join("evil.zip");
+        crafted(&archive, &[("link/planted.txt", "pwned")]);
+
+        let unpacked = extract(&archive, &into, &mut no_progress);
+
+        assert_eq!(unpacked.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(!outside.join("planted.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn never_overwrites() {
+        let dir = scratch("overwrite");
+        let archive = dir.join("a.zip");
+        crafted(&archive, &[("new.txt", "new"), ("kept.txt", "from the archive")]);
+        fs::write(dir.join("kept.txt"), "mine").unwrap();
+
+        let unpacked = extract(&archive, &dir, &mut no_progress);
+
+        assert_eq!(unpacked.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(dir.join("kept.txt")).unwrap(), "mine");
+        assert!(!dir.join("new.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cleans_up_a_failed_extraction() {
+        let dir = scratch("cleanup");
+        let archive = dir.join("a.zip");
+        crafted(&archive, &[("one.txt", "1"), ("two.txt", "2")]);
+        let into = dir.join("out");
+
+        let mut written: u64 = 0;
+        let unpacked = extract(&archive, &into, &mut |size| {
+            written += size;
+            match written > 1 {
+                true => Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled")),
+                false => Ok(()),
+            }
+        });
+
+        assert_eq!(unpacked.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert!(!into.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+use std::error::Error;
+use std::path::PathBuf;
+use std::collections::HashMap;
+use crate::config::ConfigCommands;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellError {
+    Empty,
+    UnterminatedQuote(char),
+    UnknownCommand(String),
+    MissingArgument { command: String, argument: &'static str },
+    UnexpectedArgument { command: String, argument: String },
+    UnknownFlag { command: String, flag: String },
+    InvalidArgument { argument: String, reason: String },
+    Failed(String),
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellError::Empty => write!(f, "No command given"),
+            ShellError::UnterminatedQuote(q) => write!(f, "Missing closing {q}"),
+            ShellError::UnknownCommand(c) => write!(f, "Unknown command '{c}'"),
+            ShellError::MissingArgument { command, argument } => write!(f, "{command}: missing [{argument}]"),
+            ShellError::UnexpectedArgument { command, argument } => write!(f, "{command}: unexpected argument '{argument}'"),
+            ShellError::UnknownFlag { command, flag } => write!(f, "{command}: unknown flag '{flag}'"),
+            ShellError::InvalidArgument { argument, reason } => write!(f, "'{argument}' {reason}"),
+            ShellError::Failed(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl Error for ShellError {}
+
+pub type ShellResult<T> = Result<T, ShellError>;
+
+/// Splits shell input into words.
+/// Single quotes are literal, double quotes allow `\"` and `\\`.
+/// Outside of quotes a backslash only escapes whitespace, quotes and itself,
+/// so Windows paths like `C:\Users` are left alone.
+pub fn tokenize(input: &str) -> ShellResult<Vec<String>> {
+    Ok(words(input)?.into_iter().map(|word| word.text).collect())
+}
+
+/// A word of shell input, quoted when any of it was inside quotes or escaped.
+struct Word {
+    text: String,
+    quoted: bool,
+}
+
+fn words(input: &str) -> ShellResult<Vec<Word>> {
+    let mut tokens: Vec<Word> = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                (in_token, quoted) = (true, true);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(q) => current.push(q),
+                        None => return Err(ShellError::UnterminatedQuote('\'')),
+                    }
+                }
+            },
+            '"' => {
+                (in_token, quoted) = (true, true);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"') | Some('\\')) => {
+                            current.extend(chars.next());
+                        },
+                        Some(q) => current.push(q),
+                        None => return Err(ShellError::UnterminatedQuote('"')),
+                    }
+                }
+            },
+            '\\' if matches!(chars.peek(), Some(n) if n.is_whitespace() || ['\'', '"', '\\'].contains(n)) => {
+                (in_token, quoted) = (true, true);
+                current.extend(chars.next());
+            },
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(Word { text: std::mem::take(&mut current), quoted });
+                    (in_token, quoted) = (false, false);
+                }
+            },
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_token {
+        tokens.push(Word { text: current, quoted });
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandKind { End, Exp, Ser, Scd, Del, Cpy, Opn, Mov, Idx, Grp, New, Mkd, Brn, Zip, Unz }
+
+/// What `cpy` places on the system clipboard instead of the yank register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clip { Path, Name, Contents }
+
+const CLIP_FLAGS: [(&str, Clip); 3] = [("--path", Clip::Path), ("--name", Clip::Name), ("--contents", Clip::Contents)];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    End,
+    Exp { depth: Option<usize> },
+    Ser { pattern: String, regex: bool, walk: bool },
+    Scd { target: PathBuf },
+    Del { target: Option<PathBuf>, force: bool },
+    Cpy { targets: Vec<PathBuf>, cut: bool, clip: Option<Clip> },
+    Opn { target: Option<PathBuf> },
+    Mov { target: Option<PathBuf>, destination: PathBuf },
+    Idx { build: bool, target: Option<PathBuf> },
+    Grp { pattern: String, regex: bool },
+    New { target: PathBuf, template: Option<PathBuf> },
+    Mkd { target: PathBuf },
+    Brn,
+    Zip { archive: Option<PathBuf> },
+    Unz { archive: Option<PathBuf>, destination: Option<PathBuf> },
+}
+
+/// Hands out the words following a command name one typed argument at a time.
+struct Args<'a> {
+    command: &'a str,
+    words: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl<'a> Args<'a> {
+    /// Unquoted words starting with `--` are flags, up to a lone `--` after which everything is a word.
+    fn new(command: &'a str, input: Vec<Word>) -> Self {
+        let (mut words, mut flags): (Vec<String>, Vec<String>) = (Vec::new(), Vec::new());
+        let mut input = input.into_iter();
+        for word in input.by_ref() {
+            match word {
+                Word { text, quoted: false } if text == "--" => break,
+                Word { text, quoted: false } if text.starts_with("--") => flags.push(text),
+                Word { text, .. } => words.push(text),
+            }
+        }
+        words.extend(input.map(|word| word.text));
+        Self {
+            command,
+            words,
+            flags,
+        }
+    }
+
+    fn text(&mut self, argument: &'static str) -> ShellResult<String> {
+        if self.words.is_empty() {
+            return Err(ShellError::MissingArgument { command: self.command.to_string(), argument });
+        }
+        Ok(self.words.remove(0))
+    }
+
+    fn path(&mut self, argument: &'static str) -> ShellResult<PathBuf> {
+        let raw = self.text(argument)?;
+        if raw.contains('\0') {
+            return Err(ShellError::InvalidArgument { argument: raw, reason: "is not a valid path".to_string() });
+        }
+        Ok(PathBuf::from(raw))
+    }
+
+    /// The next word as a path, when there is one.
+    fn optional_path(&mut self, argument: &'static str) -> ShellResult<Option<PathBuf>> {
+        match self.words.is_empty() {
+            true => Ok(None),
+            false => self.path(argument).map(Some),
+        }
+    }
+
+    /// The next word as a positive number, when there is one.
+    fn optional_number(&mut self, argument: &'static str) -> ShellResult<Option<usize>> {
+        if self.words.is_empty() {
+            return Ok(None);
+        }
+        let raw = self.text(argument)?;
+        match raw.parse::<usize>() {
+            Ok(number) if number > 0 => Ok(Some(number)),
+            _ => Err(ShellError::InvalidArgument { argument: raw, reason: "is not a positive number".to_string() }),
+        }
+    }
+
+    /// Every remaining word as a path, possibly none.
+    fn paths(&mut self, argument: &'static str) -> ShellResult<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        while !self.words.is_empty() {
+            paths.push(self.path(argument)?);
+        }
+        Ok(paths)
+    }
+
+    fn flag(&mut self, flag: &str) -> bool {
+        let before = self.flags.len();
+        self.flags.retain(|f| f != flag);
+        before != self.flags.len()
+    }
+
+    fn finish<T>(self, command: T) -> ShellResult<T> {
+        if let Some(flag) = self.flags.into_iter().next() {
+            return Err(ShellError::UnknownFlag { command: self.command.to_string(), flag });
+        }
+        if let Some(argument) = self.words.into_iter().next() {
+            return Err(ShellError::UnexpectedArgument { command: self.command.to_string(), argument });
+        }
+        Ok(command)
+    }
+}
+
+/// Maps the names under `[commands]` in `cnf.toml` to the commands they run.
+pub struct Registry {
+    commands: HashMap<String, CommandKind>
+}
+
+impl Registry {
+    pub fn new(names: &ConfigCommands) -> Self {
+        let commands: HashMap<String, CommandKind> = [
+            (&names.end, CommandKind::End),
+            (&names.exp, CommandKind::Exp),
+            (&names.ser, CommandKind::Ser),
+            (&names.scd, CommandKind::Scd),
+            (&names.del, CommandKind::Del),
+            (&names.cpy, CommandKind::Cpy),
+            (&names.opn, CommandKind::Opn),
+            (&names.mov, CommandKind::Mov),
+            (&names.idx, CommandKind::Idx),
+            (&names.grp, CommandKind::Grp),
+            (&names.new, CommandKind::New),
+            (&names.mkd, CommandKind::Mkd),
+            (&names.brn, CommandKind::Brn),
+            (&names.zip, CommandKind::Zip),
+            (&names.unz, CommandKind::Unz),
+        ].into_iter().map(|(name, kind)| (name.to_owned(), kind)).collect();
+
+        Self {
+            commands
+        }
+    }
+
+    pub fn parse(&self, input: &str) -> ShellResult<Command> {
+        let mut words = words(input.strip_prefix(':').unwrap_or(input))?;
+        if words.is_empty() {
+            return Err(ShellError::Empty);
+        }
+        let name = words.remove(0).text;
+        let kind = match self.commands.get(&name) {
+            Some(kind) => *kind,
+            None => return Err(ShellError::UnknownCommand(name)),
+        };
+        let mut args = Args::new(&name, words);
+
+        match kind {
+            CommandKind::End => args.finish(Command::End),
+            CommandKind::Exp => {
+                let depth = args.optional_number("depth")?;
+                args.finish(Command::Exp { depth })
+            },
+            CommandKind::Ser => {
+                let regex = args.flag("--regex");
+                let walk = args.flag("--walk");
+                let pattern = args.text("pattern")?;
+                args.finish(Command::Ser { pattern, regex, walk })
+            },
+            CommandKind::Scd => {
+                let target = args.path("path")?;
+                args.finish(Command::Scd { target })
+            },
+            CommandKind::Del => {
+                let force = args.flag("--force");
+                let target = args.optional_path("file")?;
+                args.finish(Command::Del { target, force })
+            },
+            CommandKind::Cpy => {
+                let cut = args.flag("--cut");
+                let clips: Vec<(&str, Clip)> = CLIP_FLAGS.into_iter().filter(|(flag, _)| args.flag(flag)).collect();
+                let mut exclusive = clips.iter().map(|(flag, _)| *flag).chain(cut.then_some("--cut"));
+                if let (Some(first), Some(second)) = (exclusive.next(), exclusive.next()) {
+                    return Err(ShellError::InvalidArgument {
+                        argument: second.to_string(),
+                        reason: format!("cannot be combined with {first}")
+                    });
+                }
+                let clip = clips.first().map(|(_, clip)| *clip);
+                let targets = args.paths("file")?;
+                args.finish(Command::Cpy { targets, cut, clip })
+            },
+            CommandKind::Opn => {
+                let target = args.optional_path("file")?;
+                args.finish(Command::Opn { target })
+            },
+            CommandKind::Mov => {
+                // A lone path is where the marked or selected entries go.
+                let first = args.path("path")?;
+                let (target, destination) = match args.optional_path("path")? {
+                    Some(destination) => (Some(first), destination),
+                    None => (None, first),
+                };
+                args.finish(Command::Mov { target, destination })
+            },
+            CommandKind::Grp => {
+                let regex = args.flag("--regex");
+                let pattern = args.text("pattern")?;
+                args.finish(Command::Grp { pattern, regex })
+            },
+            CommandKind::Idx => {
+                let build = args.flag("--build");
+                let target = match build {
+                    true => args.optional_path("path")?,
+                    false => None,
+                };
+                args.finish(Command::Idx { build, target })
+            },
+            CommandKind::New => {
+                let target = args.path("file")?;
+                let template = args.optional_path("template")?;
+                args.finish(Command::New { target, template })
+            },
+            CommandKind::Mkd => {
+                let target = args.path("path")?;
+                args.finish(Command::Mkd { target })
+            },
+            CommandKind::Brn => args.finish(Command::Brn),
+            CommandKind::Zip => {
+                let archive = args.optional_path("archive")?;
+                args.finish(Command::Zip { archive })
+            },
+            CommandKind::Unz => {
+                let archive = args.optional_path("archive")?;
+                let destination = args.optional_path("folder")?;
+                args.finish(Command::Unz { archive, destination })
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Registry {
+        let names: String = ["end", "exp", "ser", "scd", "del", "cpy", "opn", "mov", "idx", "grp", "new", "mkd", "brn", "zip", "unz"]
+            .iter()
+            .map(|name| format!("{name} = \"{name}\"\n"))
+            .collect();
+        Registry::new(&toml::from_str(&names).unwrap())
+    }
+
+    #[test]
+    fn quotes_and_escapes() {
+        let words = tokenize(r#"ser 'a "b"' "c \"d\" \\ e" f\ g 'h\i' C:\Users"#).unwrap();
+        assert_eq!(words, ["ser", "a \"b\"", "c \"d\" \\ e", "f g", "h\\i", "C:\\Users"]);
+    }
+
+    #[test]
+    fn empty_quotes_are_a_word() {
+        assert_eq!(tokenize("new '' \"\"").unwrap(), ["new", "", ""]);
+        assert_eq!(tokenize("  del   a  ").unwrap(), ["del", "a"]);
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        assert_eq!(tokenize("scd 'home"), Err(ShellError::UnterminatedQuote('\'')));
+        assert_eq!(tokenize("scd \"home\\\""), Err(ShellError::UnterminatedQuote('"')));
+    }
+
+    #[test]
+    fn quoted_words_are_never_flags() {
+        let registry = registry();
+        for quoted in ["del \"--force\"", "del '--force'", "del --'force'"] {
+            assert_eq!(registry.parse(quoted), Ok(Command::Del { target: Some(PathBuf::from("--force")), force: false }), "{quoted}");
+        }
+        assert_eq!(registry.parse("del --force"), Ok(Command::Del { target: None, force: true }));
+    }
+
+    #[test]
+    fn flags_stop_at_the_terminator() {
+        let registry = registry();
+        assert_eq!(registry.parse("del -- --draft"), Ok(Command::Del { target: Some(PathBuf::from("--draft")), force: false }));
+        assert_eq!(registry.parse("del --force -- --draft"), Ok(Command::Del { target: Some(PathBuf::from("--draft")), force: true }));
+        assert_eq!(registry.parse("ser -- --"), Ok(Command::Ser { pattern: "--".to_string(), regex: false, walk: false }));
+        assert_eq!(registry.parse("del '--' x"), Err(ShellError::UnexpectedArgument { command: "del".to_string(), argument: "x".to_string() }));
+    }
+
+    #[test]
+    fn flags_anywhere_before_the_terminator() {
+        let registry = registry();
+        assert_eq!(registry.parse(":ser a* --walk"), Ok(Command::Ser { pattern: "a*".to_string(), regex: false, walk: true }));
+        assert_eq!(registry.parse("del --nope a"), Err(ShellError::UnknownFlag { command: "del".to_string(), flag: "--nope".to_string() }));
+        assert_eq!(registry.parse("cpy --path --name a"), Err(ShellError::InvalidArgument {
+            argument: "--name".to_string(),
+            reason: "cannot be combined with --path".to_string()
+        }));
+    }
+}
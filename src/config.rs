@@ -1,5 +1,5 @@
-use toml;
 use std::fs;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::io::{self, Read};
 use lazy_static::lazy_static;
@@ -8,18 +8,43 @@ use serde_derive::{Deserialize, Serialize};
 lazy_static! {
     static ref CONFIG_PATH: PathBuf = PathBuf::from("src/config/cnf.toml");
     static ref TOML: String = {
-        let mut _config= fs::File::open(&*CONFIG_PATH).expect(&format!("Unable to load {}", CONFIG_PATH.display()));
+        let mut _config= fs::File::open(&*CONFIG_PATH).unwrap_or_else(|_| panic!("Unable to load {}", CONFIG_PATH.display()));
         let mut contents = String::new();
         _config.read_to_string(&mut contents)
-            .expect(&format!("Failed to parse {}", CONFIG_PATH.display()));
+            .unwrap_or_else(|_| panic!("Failed to parse {}", CONFIG_PATH.display()));
         contents
     };
 }
 
+/// Where `new` looks for templates, next to the config file.
+pub(crate) fn templates_dir() -> PathBuf {
+    CONFIG_PATH.with_file_name("templates")
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SyntaxScheme {
+    pub keyword: String,
+    pub string: String,
+    pub number: String,
+    pub comment: String,
+    pub type_name: String,
+    pub function: String,
+    pub attribute: String,
+    pub heading: String,
+    pub link: String
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ColorScheme {
     pub bg: String,
-    pub fg: String
+    pub fg: String,
+    pub syntax: SyntaxScheme
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConfigPreview {
+    pub max_lines: usize,
+    pub highlight_max_size: u64
 }
 
 #[derive(Serialize, Deserialize)]
@@ -32,6 +57,12 @@ pub struct ConfigModes {
     pub normal: String,
     pub shell: String,
     pub options: String,
+    pub trash: String,
+    pub search: String,
+    pub finder: String,
+    pub viewer: String,
+    pub rename: String,
+    pub visual: String,
     pub end: String,
 }
 
@@ -44,22 +75,44 @@ pub struct ConfigCommands {
     pub del: String,
     pub cpy: String,
     pub opn: String,
-    pub mov: String
+    pub mov: String,
+    pub idx: String,
+    pub grp: String,
+    pub new: String,
+    pub mkd: String,
+    pub brn: String,
+    pub zip: String,
+    pub unz: String,
+}
+
+/// Extensions and MIME types mapped to the command opening them.
+#[derive(Serialize, Deserialize)]
+pub struct ConfigOpeners {
+    pub terminal: HashMap<String, String>,
+    pub gui: HashMap<String, String>,
 }
 
+/// How `zip` compresses, from 0 only storing files to 9 the smallest archive.
+#[derive(Serialize, Deserialize)]
+pub struct ConfigArchive {
+    pub level: u32,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub color_schemes: ColorScheme,
     pub modes: ConfigModes,
     pub options: ConfigOptions,
-    pub commands: ConfigCommands
+    pub commands: ConfigCommands,
+    pub preview: ConfigPreview,
+    pub openers: ConfigOpeners,
+    pub archive: ConfigArchive
 }
 
 impl Config {
     pub fn new() -> io::Result<Self> {
-        let config: Config = toml::from_str(&TOML.as_str())
-            .expect(&format!("Failed to serialize from {}", CONFIG_PATH.display()));
+        let config: Config = toml::from_str(TOML.as_str())
+            .unwrap_or_else(|_| panic!("Failed to serialize from {}", CONFIG_PATH.display()));
         Ok(config)
     }
 }
\ No newline at end of file
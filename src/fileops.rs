@@ -0,0 +1,214 @@
+use std::fs;
+use std::io;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::process;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::internal::{self, WalkOptions};
+
+/// Total size in bytes of everything under `path`, symlinks are not followed.
+/// Fails as `Interrupted` once `keep_going` asks to stop.
+pub(crate) fn tree_size(path: &Path, keep_going: &(dyn Fn() -> bool + Sync)) -> io::Result<u64> {
+    let md = fs::symlink_metadata(path)?;
+    if !md.is_dir() {
+        return Ok(md.len());
+    }
+    let size = AtomicU64::new(0);
+    let everything = WalkOptions { gitignore: false, max_depth: None };
+    internal::walk(path, everything, &|entry| {
+        if let Some(md) = entry.metadata().ok().filter(|md| !md.is_dir()) {
+            size.fetch_add(md.len(), Ordering::Relaxed);
+        }
+        keep_going()
+    });
+    if !keep_going() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+    }
+    Ok(size.into_inner())
+}
+
+/// Copies a file, symlink or a whole directory tree to `dst`, keeping permissions and timestamps,
+/// calling `on_copied` with the size of every copied file.
+/// An error from `on_copied` stops the copy.
+pub(crate) fn copy_tree_with(src: &Path, dst: &Path, on_copied: &mut dyn FnMut(u64) -> io::Result<()>) -> io::Result<()> {
+    let md = fs::symlink_metadata(src)?;
+    if md.file_type().is_symlink() {
+        return copy_symlink(src, dst);
+    }
+    if md.is_dir() {
+        fs::create_dir(dst)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_tree_with(&entry.path(), &dst.join(entry.file_name()), on_copied)?;
+        }
+        // Only now, copying the children touched the folder and may need its write permission.
+        copy_times(&md, dst);
+        fs::set_permissions(dst, md.permissions())?;
+        return Ok(());
+    }
+    let copied = fs::copy(src, dst)?;
+    copy_times(&md, dst);
+    on_copied(copied)
+}
+
+/// Gives `dst` the access and modification times in `md`.
+/// Best effort, some file systems and platforms refuse to set them.
+fn copy_times(md: &fs::Metadata, dst: &Path) {
+    let mut times = fs::FileTimes::new();
+    if let Ok(accessed) = md.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    if let Ok(modified) = md.modified() {
+        times = times.set_modified(modified);
+    }
+    let _ = open_for_times(dst).and_then(|f| f.set_times(times));
+}
+
+#[cfg(not(windows))]
+fn open_for_times(path: &Path) -> io::Result<fs::File> {
+    fs::File::open(path)
+}
+
+/// Windows opens folders only with backup semantics, and a read handle cannot change times.
+#[cfg(windows)]
+fn open_for_times(path: &Path) -> io::Result<fs::File> {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    fs::OpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dst)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    // Creating symlinks needs elevated rights on Windows, copy what it points to.
+    copy_tree_with(&fs::canonicalize(src)?, dst, &mut |_| Ok(()))
+}
+
+/// Whether `a` and `b` name the very same entry, like two spellings on a case-insensitive drive.
+#[cfg(unix)]
+pub(crate) fn same_entry(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn same_entry(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Removes a file, symlink or a whole directory tree.
+pub(crate) fn remove_tree(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path)?.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    }
+}
+
+/// Renames `src` to `dst`, copying and deleting when they live on different devices.
+pub(crate) fn move_path(src: &Path, dst: &Path) -> io::Result<()> {
+    match fs::rename(src, dst) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => move_across(src, dst, &mut |_| Ok(())),
+        other => other,
+    }
+}
+
+/// Moves `src` to another device by copying it and deleting the original.
+pub(crate) fn move_across(src: &Path, dst: &Path, on_copied: &mut dyn FnMut(u64) -> io::Result<()>) -> io::Result<()> {
+    if let Err(e) = copy_tree_with(src, dst, on_copied) {
+        // Never leave half a copy behind.
+        let _ = remove_tree(dst);
+        return Err(e);
+    }
+    remove_tree(src)
+}
+
+/// The first of `name (1).ext`, `name (2).ext`... that does not exist yet.
+pub(crate) fn unique_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..).map(|n| path.with_file_name(format!("{stem} ({n}){extension}")))
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .unwrap_or(path.to_path_buf())
+}
+
+/// A free hidden name next to `path` to keep something aside for a moment.
+pub(crate) fn aside_name(path: &Path, purpose: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+    match path.with_file_name(format!(".{name}.owl-{purpose}")) {
+        taken if taken.symlink_metadata().is_ok() => unique_name(&taken),
+        free => free,
+    }
+}
+
+/// Puts `staged` where `dst` is, what `dst` held is removed only once `staged` took its place.
+/// Both are renamed inside the same folder, so this never copies.
+pub(crate) fn swap_in(staged: &Path, dst: &Path) -> io::Result<()> {
+    let replaced: PathBuf = aside_name(dst, "replaced");
+    fs::rename(dst, &replaced)?;
+    if let Err(e) = fs::rename(staged, dst) {
+        let _ = fs::rename(&replaced, dst);
+        return Err(io::Error::new(e.kind(), format!("{e}, the new one is kept at {}", staged.display())));
+    }
+    remove_tree(&replaced).map_err(|e| io::Error::new(e.kind(), format!("{e}, the old one is left at {}", replaced.display())))
+}
+
+/// Creates a new file only Owl's user can read in the temp folder, under a name nobody can guess,
+/// so a link planted in a shared temp folder is never followed.
+pub(crate) fn create_temp(prefix: &str, extension: &str) -> io::Result<(PathBuf, fs::File)> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    loop {
+        // Every RandomState is keyed at random, which is all the randomness needed here.
+        let random: u64 = RandomState::new().build_hasher().finish();
+        let path: PathBuf = std::env::temp_dir().join(format!("{prefix}-{}-{random:016x}.{extension}", process::id()));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Orders `renames` so none lands on a name that is still to be renamed away.
+/// Swaps and longer cycles go through a temporary name, the second value counts them.
+pub(crate) fn order_renames(renames: &[(PathBuf, PathBuf)]) -> (Vec<(PathBuf, PathBuf)>, usize) {
+    let mut pending: Vec<(PathBuf, PathBuf)> = renames.to_vec();
+    let mut steps: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(pending.len());
+    let mut cycles: usize = 0;
+    while !pending.is_empty() {
+        let free = pending.iter().position(|(_, to)| !pending.iter().any(|(from, _)| from == to));
+        match free {
+            Some(idx) => steps.push(pending.remove(idx)),
+            // Every target is still taken, so what is left only holds cycles, one of them is broken up.
+            None => {
+                cycles += 1;
+                let from: PathBuf = pending[0].0.to_owned();
+                let parked: PathBuf = from.with_file_name(format!(".owl-rename-{cycles}"));
+                let parked: PathBuf = match parked.symlink_metadata() {
+                    Ok(_) => unique_name(&parked),
+                    Err(_) => parked,
+                };
+                steps.push((from, parked.to_owned()));
+                pending[0].0 = parked;
+            },
+        }
+    }
+    (steps, cycles)
+}
@@ -0,0 +1,525 @@
+use std::str::CharIndices;
+use std::iter::Peekable;
+
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while"
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax { Rust, Toml, Json, Markdown }
+
+impl Syntax {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "rs" => Some(Syntax::Rust),
+            "toml" => Some(Syntax::Toml),
+            "json" => Some(Syntax::Json),
+            "md" | "markdown" => Some(Syntax::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Type,
+    Function,
+    Attribute,
+    Key,
+    Heading,
+    Code,
+    Link,
+    Emphasis,
+}
+
+/// A highlighted byte range of a single line.
+#[derive(Debug, Clone, Copy)]
+pub struct Highlight {
+    pub token: Token,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What is still open when a line ends.
+#[derive(Clone, Copy, PartialEq)]
+enum Carry {
+    Nothing,
+    BlockComment(usize),
+    Str,
+    RawStr(usize),
+    TripleStr(char),
+    Fence,
+}
+
+struct Lexer<'a> {
+    line: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    highlights: Vec<Highlight>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            line,
+            chars: line.char_indices().peekable(),
+            highlights: Vec::new(),
+        }
+    }
+
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map(|(i, _)| *i).unwrap_or(self.line.len())
+    }
+
+    fn rest(&mut self) -> &'a str {
+        let pos = self.pos();
+        &self.line[pos..]
+    }
+
+    fn mark(&mut self, token: Token, start: usize) {
+        let end = self.pos();
+        if end > start {
+            self.highlights.push(Highlight { token, start, end });
+        }
+    }
+
+    fn skip(&mut self, n: usize) {
+        for _ in 0..n {
+            self.chars.next();
+        }
+    }
+
+    fn skip_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.chars.next_if(|(_, c)| f(*c)).is_some() {}
+    }
+
+    fn skip_to_end(&mut self) {
+        while self.chars.next().is_some() {}
+    }
+
+    /// Consumes up to and including the closing `quote`, honouring backslashes.
+    /// Returns false when the line ends first.
+    fn close_quote(&mut self, quote: char, escapes: bool) -> bool {
+        while let Some((_, c)) = self.chars.next() {
+            if escapes && c == '\\' {
+                self.chars.next();
+            } else if c == quote {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Consumes up to and including `end`, returns false when the line ends first.
+    fn close_with(&mut self, end: &str) -> bool {
+        match self.rest().find(end) {
+            Some(at) => {
+                let target = self.pos() + at + end.len();
+                while self.pos() < target {
+                    self.chars.next();
+                }
+                true
+            },
+            None => {
+                self.skip_to_end();
+                false
+            }
+        }
+    }
+
+    fn number(&mut self) {
+        let start = self.pos();
+        self.skip_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        // Only treat the dot as a decimal point, `0..10` is a range.
+        if self.rest().starts_with('.') && self.rest()[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            self.skip(1);
+            self.skip_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        }
+        self.mark(Token::Number, start);
+    }
+
+    fn word(&mut self) -> &'a str {
+        let start = self.pos();
+        self.skip_while(|c| c.is_alphanumeric() || c == '_');
+        &self.line[start..self.pos()]
+    }
+}
+
+fn rust(line: &str, carry: Carry) -> (Vec<Highlight>, Carry) {
+    let mut lx = Lexer::new(line);
+    let mut carry = carry;
+    // Where the comment or string that is still open started on this line.
+    let mut opened: Option<usize> = None;
+
+    loop {
+        let start = opened.take().unwrap_or(lx.pos());
+        match carry {
+            Carry::BlockComment(depth) => {
+                let mut depth = depth;
+                while depth > 0 && lx.chars.peek().is_some() {
+                    if lx.rest().starts_with("/*") {
+                        depth += 1;
+                        lx.skip(2);
+                    } else if lx.rest().starts_with("*/") {
+                        depth -= 1;
+                        lx.skip(2);
+                    } else {
+                        lx.skip(1);
+                    }
+                }
+                lx.mark(Token::Comment, start);
+                carry = if depth > 0 { Carry::BlockComment(depth) } else { Carry::Nothing };
+                if depth > 0 {
+                    break;
+                }
+                continue;
+            },
+            Carry::Str => {
+                let closed = lx.close_quote('"', true);
+                lx.mark(Token::String, start);
+                if !closed {
+                    break;
+                }
+                carry = Carry::Nothing;
+                continue;
+            },
+            Carry::RawStr(hashes) => {
+                let closed = lx.close_with(&format!("\"{}", "#".repeat(hashes)));
+                lx.mark(Token::String, start);
+                if !closed {
+                    break;
+                }
+                carry = Carry::Nothing;
+                continue;
+            },
+            _ => {},
+        }
+
+        let c = match lx.chars.peek() {
+            Some((_, c)) => *c,
+            None => break,
+        };
+        let rest = lx.rest();
+        if rest.starts_with("//") {
+            lx.skip_to_end();
+            lx.mark(Token::Comment, start);
+        } else if rest.starts_with("/*") {
+            lx.skip(2);
+            carry = Carry::BlockComment(1);
+            opened = Some(start);
+        } else if rest.starts_with('"') || rest.starts_with("b\"") {
+            lx.skip(if c == 'b' { 2 } else { 1 });
+            carry = Carry::Str;
+            opened = Some(start);
+        } else if let Some(hashes) = rest.strip_prefix('r')
+            .map(|r| r.len() - r.trim_start_matches('#').len())
+            .filter(|h| rest[1 + h..].starts_with('"'))
+        {
+            lx.skip(2 + hashes);
+            carry = Carry::RawStr(hashes);
+            opened = Some(start);
+        } else if c == '\'' {
+            // Either a char literal or a lifetime.
+            let literal = rest.len() > 2 && (rest[1..].starts_with('\\') || rest[1..].chars().nth(1) == Some('\''));
+            lx.skip(1);
+            if literal {
+                lx.close_quote('\'', true);
+                lx.mark(Token::String, start);
+            } else {
+                lx.word();
+                lx.mark(Token::Type, start);
+            }
+        } else if rest.starts_with("#[") || rest.starts_with("#![") {
+            lx.close_with("]");
+            lx.mark(Token::Attribute, start);
+        } else if c.is_ascii_digit() {
+            lx.number();
+        } else if c.is_alphabetic() || c == '_' {
+            let word = lx.word();
+            let next = lx.rest();
+            let token = if RUST_KEYWORDS.contains(&word) {
+                Some(Token::Keyword)
+            } else if next.starts_with('!') {
+                lx.skip(1);
+                Some(Token::Function)
+            } else if next.starts_with('(') {
+                Some(Token::Function)
+            } else if word.starts_with(char::is_uppercase) {
+                Some(Token::Type)
+            } else {
+                None
+            };
+            if let Some(token) = token {
+                lx.mark(token, start);
+            }
+        } else {
+            lx.skip(1);
+        }
+    }
+    (lx.highlights, carry)
+}
+
+fn json(line: &str) -> Vec<Highlight> {
+    let mut lx = Lexer::new(line);
+    while let Some((start, c)) = lx.chars.peek().copied() {
+        if c == '"' {
+            lx.skip(1);
+            lx.close_quote('"', true);
+            let is_key = lx.rest().trim_start().starts_with(':');
+            lx.mark(if is_key { Token::Key } else { Token::String }, start);
+        } else if c == '-' || c.is_ascii_digit() {
+            lx.skip(1);
+            lx.skip_while(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'));
+            lx.mark(Token::Number, start);
+        } else if c.is_alphabetic() {
+            if matches!(lx.word(), "true" | "false" | "null") {
+                lx.mark(Token::Keyword, start);
+            }
+        } else {
+            lx.skip(1);
+        }
+    }
+    lx.highlights
+}
+
+fn toml(line: &str, carry: Carry) -> (Vec<Highlight>, Carry) {
+    let mut lx = Lexer::new(line);
+    let mut carry = carry;
+
+    if let Carry::TripleStr(quote) = carry {
+        let closed = lx.close_with(&quote.to_string().repeat(3));
+        lx.mark(Token::String, 0);
+        if !closed {
+            return (lx.highlights, carry);
+        }
+        carry = Carry::Nothing;
+    }
+
+    let trimmed = lx.rest().trim_start();
+    if lx.pos() == 0 && trimmed.starts_with('[') {
+        let start = line.len() - trimmed.len();
+        let end = line[..line.find('#').unwrap_or(line.len())].trim_end().len();
+        lx.highlights.push(Highlight { token: Token::Heading, start, end });
+        lx.skip_to_end();
+    } else if lx.pos() == 0 && !trimmed.starts_with('#') {
+        if let Some(eq) = line.find('=') {
+            let start = line.len() - trimmed.len();
+            let end = line[..eq].trim_end().len();
+            // A line like `  = x` has no key at all.
+            if end > start {
+                lx.highlights.push(Highlight { token: Token::Key, start, end });
+            }
+            while lx.pos() <= eq {
+                lx.chars.next();
+            }
+        }
+    }
+
+    while let Some((start, c)) = lx.chars.peek().copied() {
+        let rest = lx.rest();
+        if c == '#' {
+            lx.skip_to_end();
+            lx.mark(Token::Comment, start);
+        } else if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+            lx.skip(3);
+            let closed = lx.close_with(&rest[..3]);
+            lx.mark(Token::String, start);
+            if !closed {
+                carry = Carry::TripleStr(c);
+            }
+        } else if c == '"' || c == '\'' {
+            lx.skip(1);
+            lx.close_quote(c, c == '"');
+            lx.mark(Token::String, start);
+        } else if c.is_ascii_digit() || ((c == '+' || c == '-') && rest[1..].starts_with(|n: char| n.is_ascii_digit())) {
+            lx.skip(1);
+            lx.skip_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-' | '+'));
+            lx.mark(Token::Number, start);
+        } else if c.is_alphabetic() {
+            if matches!(lx.word(), "true" | "false" | "inf" | "nan") {
+                lx.mark(Token::Keyword, start);
+            }
+        } else {
+            lx.skip(1);
+        }
+    }
+    (lx.highlights, carry)
+}
+
+fn markdown(line: &str, carry: Carry) -> (Vec<Highlight>, Carry) {
+    let trimmed = line.trim_start();
+    let whole = |token: Token| vec![Highlight { token, start: 0, end: line.len() }];
+
+    if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+        let carry = if carry == Carry::Fence { Carry::Nothing } else { Carry::Fence };
+        return (whole(Token::Comment), carry);
+    }
+    if carry == Carry::Fence {
+        return (whole(Token::Code), carry);
+    }
+    if trimmed.starts_with('#') {
+        return (whole(Token::Heading), carry);
+    }
+    if trimmed.starts_with('>') {
+        return (whole(Token::Comment), carry);
+    }
+
+    let mut lx = Lexer::new(line);
+    lx.skip_while(char::is_whitespace);
+    let marker_start = lx.pos();
+    let rest = lx.rest();
+    if rest.starts_with("- ") || rest.starts_with("* ") || rest.starts_with("+ ") {
+        lx.skip(1);
+        lx.mark(Token::Keyword, marker_start);
+    } else if rest.find(". ").is_some_and(|dot| dot > 0 && rest[..dot].chars().all(|c| c.is_ascii_digit())) {
+        lx.skip_while(|c| c.is_ascii_digit());
+        lx.skip(1);
+        lx.mark(Token::Keyword, marker_start);
+    }
+
+    while let Some((start, c)) = lx.chars.peek().copied() {
+        let rest = lx.rest();
+        if c == '`' {
+            lx.skip(1);
+            lx.close_quote('`', false);
+            lx.mark(Token::Code, start);
+        } else if c == '[' && rest.contains("](") {
+            lx.close_with(")");
+            lx.mark(Token::Link, start);
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            lx.skip(2);
+            lx.close_with(&rest[..2]);
+            lx.mark(Token::Emphasis, start);
+        } else if (c == '*' || c == '_')
+            && !line[..start].ends_with(char::is_alphanumeric)
+            && rest[1..].find(c).is_some_and(|end| end > 0)
+        {
+            lx.skip(1);
+            lx.close_quote(c, false);
+            lx.mark(Token::Emphasis, start);
+        } else {
+            lx.skip(1);
+        }
+    }
+    (lx.highlights, carry)
+}
+
+/// Highlights consecutive lines of a file, starting at its first line.
+pub fn highlight(syntax: Syntax, lines: &[String]) -> Vec<Vec<Highlight>> {
+    let mut carry = Carry::Nothing;
+    lines.iter().map(|line| {
+        let (highlights, next) = match syntax {
+            Syntax::Rust => rust(line, carry),
+            Syntax::Toml => toml(line, carry),
+            Syntax::Json => (json(line), Carry::Nothing),
+            Syntax::Markdown => markdown(line, carry),
+        };
+        carry = next;
+        highlights
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The highlighted text of every line, checking the spans are in order and on char boundaries.
+    fn spans<'a>(syntax: Syntax, lines: &[&'a str]) -> Vec<Vec<(Token, &'a str)>> {
+        let owned: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        highlight(syntax, &owned).into_iter().zip(lines).map(|(highlights, line)| {
+            let mut cursor: usize = 0;
+            highlights.into_iter().map(|h| {
+                assert!(cursor <= h.start && h.start < h.end && h.end <= line.len(), "{h:?} in {line:?}");
+                assert!(line.is_char_boundary(h.start) && line.is_char_boundary(h.end), "{h:?} in {line:?}");
+                cursor = h.end;
+                (h.token, &line[h.start..h.end])
+            }).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn rust_strings_with_escapes() {
+        assert_eq!(spans(Syntax::Rust, &[r#"let s = "a \"b\" \\";"#, r##"r#"say "hi""#"##]), [
+            vec![(Token::Keyword, "let"), (Token::String, r#""a \"b\" \\""#)],
+            vec![(Token::String, r##"r#"say "hi""#"##)],
+        ]);
+    }
+
+    #[test]
+    fn rust_nested_comments_span_lines() {
+        assert_eq!(spans(Syntax::Rust, &["/* a /* b */ still", "c */ fn x() {}"]), [
+            vec![(Token::Comment, "/* a /* b */ still")],
+            vec![(Token::Comment, "c */"), (Token::Keyword, "fn"), (Token::Function, "x")],
+        ]);
+    }
+
+    #[test]
+    fn rust_unterminated_string_carries_on() {
+        assert_eq!(spans(Syntax::Rust, &["let s = \"open", "still\" + 1"]), [
+            vec![(Token::Keyword, "let"), (Token::String, "\"open")],
+            vec![(Token::String, "still\""), (Token::Number, "1")],
+        ]);
+    }
+
+    #[test]
+    fn rust_multibyte_text() {
+        assert_eq!(spans(Syntax::Rust, &["let é = 'ü'; // ñ", "fn f<'a>(x: &'a str)"]), [
+            vec![(Token::Keyword, "let"), (Token::String, "'ü'"), (Token::Comment, "// ñ")],
+            vec![(Token::Keyword, "fn"), (Token::Type, "'a"), (Token::Type, "'a")],
+        ]);
+    }
+
+    #[test]
+    fn toml_keys_strings_and_comments() {
+        assert_eq!(spans(Syntax::Toml, &[r#"name = "x \" y" # c"#, "clé = \"ü\"", "  = x", "[tool.owl] # x"]), [
+            vec![(Token::Key, "name"), (Token::String, r#""x \" y""#), (Token::Comment, "# c")],
+            vec![(Token::Key, "clé"), (Token::String, "\"ü\"")],
+            vec![],
+            vec![(Token::Heading, "[tool.owl]")],
+        ]);
+    }
+
+    #[test]
+    fn toml_multiline_strings() {
+        assert_eq!(spans(Syntax::Toml, &["s = \"\"\"one", "two\"\"\" # done"]), [
+            vec![(Token::Key, "s"), (Token::String, "\"\"\"one")],
+            vec![(Token::String, "two\"\"\""), (Token::Comment, "# done")],
+        ]);
+    }
+
+    #[test]
+    fn json_keys_and_values() {
+        assert_eq!(spans(Syntax::Json, &[r#"{"ké": "v\"", "n": -1.5e3, "t": true}"#]), [vec![
+            (Token::Key, "\"ké\""),
+            (Token::String, r#""v\"""#),
+            (Token::Key, "\"n\""),
+            (Token::Number, "-1.5e3"),
+            (Token::Key, "\"t\""),
+            (Token::Keyword, "true"),
+        ]]);
+    }
+
+    #[test]
+    fn markdown_inline_and_fences() {
+        let lines = ["- **gras** and *ünï* with `code` [lien](url)", "```", "let x = 1;", "```", "# Titre"];
+        assert_eq!(spans(Syntax::Markdown, &lines), [
+            vec![
+                (Token::Keyword, "-"),
+                (Token::Emphasis, "**gras**"),
+                (Token::Emphasis, "*ünï*"),
+                (Token::Code, "`code`"),
+                (Token::Link, "[lien](url)"),
+            ],
+            vec![(Token::Comment, "```")],
+            vec![(Token::Code, "let x = 1;")],
+            vec![(Token::Comment, "```")],
+            vec![(Token::Heading, "# Titre")],
+        ]);
+    }
+}
@@ -0,0 +1,317 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+use bplustree::BPlusTree;
+use chrono::{DateTime, Local};
+use crate::internal::{self, WalkOptions};
+use crate::search::Pattern;
+
+const HEADER: &str = "owl index 1";
+
+// The index holds what `ser` would find, nothing more.
+const INDEXED: WalkOptions = WalkOptions { gitignore: true, max_depth: None };
+
+/// What the index remembers about an indexed folder, to tell whether it changed since.
+struct Folder {
+    modified: u128,
+    children: Vec<String>,
+}
+
+/// Every name below `root`, kept in B+ trees so name and prefix lookups never touch the disk.
+/// Paths that are not valid UTF-8 are left out.
+pub struct Index {
+    pub root: PathBuf,
+    pub refreshed: DateTime<Local>,
+    /// `lowercase name \0 full path`, so names sharing a prefix sit next to each other.
+    names: BPlusTree<String, ()>,
+    /// Full path of every indexed folder, the root included.
+    folders: BPlusTree<String, Folder>,
+}
+
+pub enum IndexState {
+    Missing,
+    /// The saved index is being caught up with the disk.
+    Refreshing,
+    Ready(Index),
+}
+
+/// Where the index of the last `idx --build` is kept between runs.
+pub(crate) fn location(home: &Path) -> PathBuf {
+    let data_home: PathBuf = ["XDG_DATA_HOME", "LOCALAPPDATA"].iter()
+        .find_map(|var| env::var_os(var).filter(|d| !d.is_empty()))
+        .map(PathBuf::from)
+        .unwrap_or(home.join(".local/share"));
+    data_home.join("owl").join("index")
+}
+
+fn name_key(name: &str, path: &str) -> String {
+    format!("{}\0{path}", name.to_lowercase())
+}
+
+fn modified(md: &fs::Metadata) -> u128 {
+    md.modified().ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+fn escape(raw: &str) -> String {
+    raw.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t")
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut unescaped = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(other) => unescaped.push(other),
+                None => {},
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+impl Index {
+    fn empty(root: PathBuf) -> Self {
+        Self {
+            root,
+            refreshed: Local::now(),
+            names: BPlusTree::new(),
+            folders: BPlusTree::new(),
+        }
+    }
+
+    /// Indexes everything below `root`, `keep_going` gets the number of names so far and can stop it.
+    pub fn build(root: PathBuf, keep_going: &(dyn Fn(u64) -> bool + Sync)) -> io::Result<Self> {
+        let index = Self::empty(root);
+        let root = index.root.clone();
+        index.add_tree(&root, keep_going)?;
+        Ok(index)
+    }
+
+    /// Indexes `dir` and everything below it.
+    fn add_tree(&self, dir: &Path, keep_going: &(dyn Fn(u64) -> bool + Sync)) -> io::Result<()> {
+        let dir_key: String = dir.to_str()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidData, "the path is not valid UTF-8"))?
+            .to_owned();
+        let dir_modified: u128 = modified(&fs::metadata(dir)?);
+        // Folders are written out once the walk is over and all of their children are known.
+        let found: Mutex<HashMap<String, (u128, Vec<String>)>> = Mutex::new(HashMap::new());
+        found.lock().unwrap().insert(dir_key, (dir_modified, Vec::new()));
+        let count = AtomicU64::new(0);
+
+        internal::walk_inside(&self.root, dir, INDEXED, &|entry| {
+            let path: PathBuf = entry.path();
+            let (name, full, parent) = match (entry.file_name().to_str(), path.to_str(), path.parent().and_then(Path::to_str)) {
+                (Some(name), Some(full), Some(parent)) => (name.to_owned(), full.to_owned(), parent.to_owned()),
+                _ => return true,
+            };
+            self.names.insert(name_key(&name, &full), ());
+            let mut found = found.lock().unwrap();
+            found.entry(parent).or_default().1.push(name);
+            if let Some(md) = entry.metadata().ok().filter(|md| md.is_dir()) {
+                found.entry(full).or_default().0 = modified(&md);
+            }
+            drop(found);
+            keep_going(count.fetch_add(1, Ordering::Relaxed) + 1)
+        });
+        for (path, (modified, children)) in found.into_inner().unwrap() {
+            self.folders.insert(path, Folder { modified, children });
+        }
+        Ok(())
+    }
+
+    /// Drops `folder` and everything below it.
+    fn forget(&self, folder: &str) {
+        let children: Vec<String> = match self.folders.remove(folder) {
+            Some(record) => record.children,
+            None => return,
+        };
+        for child in children {
+            let full: String = format!("{}", Path::new(folder).join(&child).display());
+            self.names.remove(&name_key(&child, &full));
+            self.forget(&full);
+        }
+    }
+
+    /// Reads `folder` again, indexing what appeared and forgetting what is gone.
+    fn reread(&self, folder: &str, modified_now: u128) {
+        let old: Vec<String> = self.folders.lookup(folder, |f| f.children.clone()).unwrap_or_default();
+        let current: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+        let just_this = WalkOptions { max_depth: Some(1), ..INDEXED };
+        internal::walk_inside(&self.root, Path::new(folder), just_this, &|entry| {
+            if let Some(name) = entry.file_name().to_str() {
+                let is_dir: bool = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                current.lock().unwrap().push((name.to_owned(), is_dir));
+            }
+            true
+        });
+        let current: Vec<(String, bool)> = current.into_inner().unwrap();
+
+        for gone in old.iter().filter(|o| !current.iter().any(|(c, _)| c == *o)) {
+            let full: String = format!("{}", Path::new(folder).join(gone).display());
+            self.names.remove(&name_key(gone, &full));
+            self.forget(&full);
+        }
+        for (new, is_dir) in current.iter().filter(|(c, _)| !old.contains(c)) {
+            let full: PathBuf = Path::new(folder).join(new);
+            self.names.insert(name_key(new, &full.display().to_string()), ());
+            // A new folder that cannot be read is left out, the rest of the index still catches up.
+            if *is_dir {
+                let _ = self.add_tree(&full, &|_| true);
+            }
+        }
+        let children: Vec<String> = current.into_iter().map(|(name, _)| name).collect();
+        self.folders.insert(folder.to_owned(), Folder { modified: modified_now, children });
+    }
+
+    /// Catches up with the disk, only folders modified since the last refresh are read again.
+    /// Folders that went missing or cannot be read anymore are forgotten.
+    pub fn refresh(&mut self) {
+        let mut known: Vec<(String, u128)> = Vec::new();
+        let mut iter = self.folders.raw_iter();
+        iter.seek_to_first();
+        while let Some((path, folder)) = iter.next() {
+            known.push((path.to_owned(), folder.modified));
+        }
+        drop(iter);
+
+        // Sorted paths put parents first, so a removed folder is forgotten before its children come up.
+        for (path, last_modified) in known {
+            if self.folders.lookup(path.as_str(), |_| ()).is_none() {
+                continue;
+            }
+            match fs::metadata(&path).map(|md| modified(&md)) {
+                Ok(now) if now == last_modified => {},
+                Ok(now) => self.reread(&path, now),
+                Err(_) => self.forget(&path),
+            }
+        }
+        self.refreshed = Local::now();
+    }
+
+    /// Indexed paths below `dir` whose name matches `pattern`.
+    pub fn search(&self, dir: &Path, pattern: &Pattern) -> Vec<PathBuf> {
+        let prefix: String = pattern.prefix().unwrap_or_default();
+        let mut found: Vec<PathBuf> = Vec::new();
+        let mut iter = self.names.raw_iter();
+        iter.seek(prefix.as_str());
+        while let Some((key, _)) = iter.next() {
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            let path: &Path = match key.split_once('\0') {
+                Some((_, path)) => Path::new(path),
+                None => continue,
+            };
+            let matched: bool = path.file_name().is_some_and(|name| pattern.matches(&name.to_string_lossy()));
+            if matched && path.starts_with(dir) && path != dir {
+                found.push(path.to_path_buf());
+            }
+        }
+        found
+    }
+
+    pub fn covers(&self, dir: &Path) -> bool {
+        dir.starts_with(&self.root)
+    }
+
+    /// How many names and folders are indexed.
+    pub fn counts(&self) -> (usize, usize) {
+        (self.names.len(), self.folders.len())
+    }
+
+    /// Writes the index next to where it is kept, then swaps it in.
+    pub fn save(&self, location: &Path) -> io::Result<()> {
+        if let Some(parent) = location.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let partial: PathBuf = location.with_extension("partial");
+        let mut out = BufWriter::new(fs::File::create(&partial)?);
+        writeln!(out, "{HEADER}")?;
+        writeln!(out, "{}", escape(&self.root.display().to_string()))?;
+        writeln!(out, "{}", self.refreshed.to_rfc3339())?;
+
+        let mut iter = self.folders.raw_iter();
+        iter.seek_to_first();
+        while let Some((path, folder)) = iter.next() {
+            writeln!(out, "{}\t{}", folder.modified, escape(path))?;
+            for child in &folder.children {
+                writeln!(out, "\t{}", escape(child))?;
+            }
+        }
+        drop(iter);
+        out.into_inner()?.sync_all()?;
+        fs::rename(partial, location)
+    }
+
+    pub fn load(location: &Path) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("{} is not an index", location.display()));
+        let mut lines = io::BufReader::new(fs::File::open(location)?).lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid());
+        }
+        let root: PathBuf = PathBuf::from(unescape(&lines.next().transpose()?.ok_or_else(invalid)?));
+        let refreshed: DateTime<Local> = lines.next().transpose()?
+            .and_then(|r| DateTime::parse_from_rfc3339(&r).ok())
+            .ok_or_else(invalid)?
+            .with_timezone(&Local);
+        let mut index = Self::empty(root);
+        index.refreshed = refreshed;
+
+        let mut folder: Option<(String, Folder)> = None;
+        for line in lines {
+            let line = line?;
+            if let Some(child) = line.strip_prefix('\t') {
+                let (path, record) = folder.as_mut().ok_or_else(invalid)?;
+                let child = unescape(child);
+                let full: String = format!("{}", Path::new(path.as_str()).join(&child).display());
+                index.names.insert(name_key(&child, &full), ());
+                record.children.push(child);
+                continue;
+            }
+            let (modified, path) = line.split_once('\t').ok_or_else(invalid)?;
+            let modified: u128 = modified.parse().map_err(|_| invalid())?;
+            if let Some((path, record)) = folder.replace((unescape(path), Folder { modified, children: Vec::new() })) {
+                index.folders.insert(path, record);
+            }
+        }
+        if let Some((path, record)) = folder {
+            index.folders.insert(path, record);
+        }
+        Ok(index)
+    }
+}
+
+/// Loads the saved index and catches it up with the disk without holding Owl back.
+pub(crate) fn refresh_in_background(location: PathBuf, state: Arc<Mutex<IndexState>>) {
+    if !location.is_file() {
+        return;
+    }
+    *state.lock().unwrap() = IndexState::Refreshing;
+    thread::spawn(move || {
+        let refreshed = Index::load(&location).map(|mut index| {
+            index.refresh();
+            index
+        });
+        let mut state = state.lock().unwrap();
+        // An index built in the meantime is newer, keep it.
+        if let IndexState::Refreshing = *state {
+            *state = match refreshed.and_then(|index| index.save(&location).map(|_| index)) {
+                Ok(index) => IndexState::Ready(index),
+                Err(_) => IndexState::Missing,
+            };
+        }
+    });
+}
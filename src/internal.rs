@@ -6,17 +6,22 @@ use std::fmt;
 use std::ffi::OsStr;
 use std::fs::Permissions;
 use std::error::Error;
-use std::sync::Arc;
-use std::time::SystemTime;
-use std::path::{PathBuf, Display};
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use std::path::{Component, Path, PathBuf, Display};
 use chrono::offset::Utc;
 use chrono::DateTime;
 use humansize::{make_format, DECIMAL};
-use winsafe::{self as w, co::ERROR, SysResult};
+use crossbeam_deque::{Injector, Stealer, Worker};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use crate::platform::PlatformError;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum BootError {
-    DriveLoadingFailed(ERROR),
+    DriveLoadingFailed(PlatformError),
     ConfigLoadingFailed
 }
 
@@ -40,24 +45,62 @@ impl Error for BootError {
 
 pub type BootResult<T> = Result<T, BootError>;
 
-pub(crate) fn drives() -> SysResult<Vec<PathBuf>> {
-    if w::GetLogicalDrives() == 0 {
-        return Err(w::GetLastError())
+pub(crate) fn human_time(sys_time: io::Result<SystemTime>) -> io::Result<String> {
+    let datetime: DateTime<Utc> = DateTime::from(sys_time?);
+    let formatted: String = format!("{}", datetime.format("%d/%m/%Y %H:%M"));
+    Ok(formatted)
+}
+
+/// Resolves `.` and `..` without touching the disk, the same way `cd` does.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                // The root is its own parent.
+                if normalized.parent().is_some() {
+                    normalized.pop();
+                }
+            },
+            other => normalized.push(other.as_os_str()),
+        }
     }
-    let logical_drives: Vec<String> = w::GetLogicalDriveStrings()?;
-    Ok(logical_drives.into_iter().map(|p| PathBuf::from(p)).collect::<Vec<PathBuf>>())
+    normalized
 }
 
-pub(crate) fn home_drive() -> SysResult<PathBuf> {
-    let available_drives: Vec<PathBuf> = drives()?;
-    let home_drive: &PathBuf = available_drives.first().unwrap();
-    Ok(home_drive.to_owned())
+#[cfg(not(windows))]
+const INVALID_NAME_CHARS: &[char] = &['/', '\0'];
+#[cfg(windows)]
+const INVALID_NAME_CHARS: &[char] = &['/', '\\', '<', '>', ':', '"', '|', '?', '*', '\0'];
+
+/// Why `name` cannot name a file, if it cannot.
+pub(crate) fn invalid_name(name: &str) -> Option<String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Some("is not a valid name".to_string());
+    }
+    name.chars()
+        .find(|c| INVALID_NAME_CHARS.contains(c) || c.is_control())
+        .map(|c| format!("cannot contain {c:?}"))
 }
 
-pub(crate) fn human_time(sys_time: io::Result<SystemTime>) -> io::Result<String> {
-    let datetime: DateTime<Utc> = DateTime::from(sys_time?);
-    let formatted: String = format!("{}", datetime.format("%d/%m/%Y %H:%M"));
-    Ok(formatted)
+#[cfg(unix)]
+pub(crate) fn human_permissions(permissions: &Permissions) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = permissions.mode();
+    let rwx: String = (0..9).rev().map(|bit| match mode & (1 << bit) != 0 {
+        true => ['x', 'w', 'r'][bit % 3],
+        false => '-',
+    }).collect();
+    format!("{rwx} ({:o})", mode & 0o777)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn human_permissions(permissions: &Permissions) -> String {
+    match permissions.readonly() {
+        true => "Read only".to_string(),
+        false => "Read/Write".to_string(),
+    }
 }
 
 pub(crate) fn human_size(size: u64) -> String {
@@ -71,6 +114,9 @@ pub type DateCreation = io::Result<String>;
 
 pub struct Node {
     pub root_path: Arc<PathBuf>,
+    /// How far below the cwd the node sits in the tree view, 0 for its children.
+    pub depth: usize,
+    pub expanded: bool,
     pub size: u64,
     pub is_file: bool,
     pub is_dir: bool,
@@ -119,6 +165,8 @@ impl Node {
 
         Self {
             root_path,
+            depth: 0,
+            expanded: false,
             size,
             is_file,
             is_dir,
@@ -138,26 +186,129 @@ impl Node {
 
 pub struct Directory {
     parent: Arc<PathBuf>,
-    nodes: Vec<Node>
+    nodes: Vec<Node>,
+    /// Set while the cwd is shown as a tree, how many levels deep it may be expanded.
+    tree_depth: Option<usize>,
+}
+
+fn read_nodes(dir: &Path, depth: usize) -> io::Result<Vec<Node>> {
+    Ok(fs::read_dir(dir)?.filter_map(
+        |rd| rd.map(|e| Node { depth, ..Node::from(Arc::new(e.path())) }).ok()
+    ).collect())
 }
 
 impl Directory {
-    pub fn from(path: Arc<PathBuf>) -> Self {
+    pub fn from(path: Arc<PathBuf>) -> io::Result<Self> {
         let parent = path;
-        let nodes: Vec<Node> = fs::read_dir(parent.as_ref()).unwrap().map(
-            |rd| rd.map(|e| Node::from(Arc::new(e.path()))).unwrap()
-        ).collect();
+        let nodes: Vec<Node> = read_nodes(&parent, 0)?;
         
-        Self {
+        Ok(Self {
             parent,
             nodes,
+            tree_depth: None,
+        })
+    }
+
+    pub fn tree_depth(&self) -> Option<usize> {
+        self.tree_depth
+    }
+
+    /// Shows the directory as a tree at most `depth` levels deep, or flat again when `None`.
+    pub fn set_tree(&mut self, depth: Option<usize>) {
+        self.tree_depth = depth;
+        let limit: usize = depth.unwrap_or(1);
+        let mut idx = 0;
+        while idx < self.nodes.len() {
+            if self.nodes[idx].expanded && self.nodes[idx].depth + 1 >= limit {
+                self.collapse(idx);
+            }
+            idx += 1;
+        }
+    }
+
+    /// Reads the children of the folder at `idx` into the tree, right below it.
+    /// Returns whether anything was expanded.
+    pub fn expand(&mut self, idx: usize) -> io::Result<bool> {
+        let (limit, node) = match (self.tree_depth, self.nodes.get(idx)) {
+            (Some(limit), Some(node)) => (limit, node),
+            _ => return Ok(false),
+        };
+        if !node.is_dir || node.expanded || node.depth + 1 >= limit {
+            return Ok(false);
+        }
+        let children: Vec<Node> = read_nodes(&node.root_path, node.depth + 1)?;
+        self.nodes[idx].expanded = true;
+        self.nodes.splice(idx + 1..idx + 1, children);
+        Ok(true)
+    }
+
+    /// Drops everything below the folder at `idx` from the tree.
+    pub fn collapse(&mut self, idx: usize) {
+        let depth: usize = match self.nodes.get_mut(idx) {
+            Some(node) => {
+                node.expanded = false;
+                node.depth
+            },
+            None => return,
+        };
+        let end: usize = self.nodes[idx + 1..].iter()
+            .position(|n| n.depth <= depth)
+            .map_or(self.nodes.len(), |p| idx + 1 + p);
+        self.nodes.drain(idx + 1..end);
+    }
+
+    /// Shows the tree `depth` levels deep made of `nodes`, listed the way `read_tree` does.
+    pub fn set_tree_nodes(&mut self, depth: usize, nodes: Vec<Node>) {
+        self.tree_depth = Some(depth);
+        self.nodes = nodes;
+    }
+
+    /// Paths of the expanded folders, parents before their children.
+    pub fn expanded(&self) -> Vec<PathBuf> {
+        self.nodes.iter().filter(|n| n.expanded).map(|n| n.root_path.to_path_buf()).collect()
+    }
+
+    /// Expands `folders` again after the directory was read anew, those that are gone are skipped.
+    pub fn restore(&mut self, tree_depth: Option<usize>, folders: &[PathBuf]) {
+        self.tree_depth = tree_depth;
+        for folder in folders {
+            if let Some(idx) = self.position(folder) {
+                let _ = self.expand(idx);
+            }
         }
     }
 
+    pub fn path(&self) -> &Arc<PathBuf> {
+        &self.parent
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Node> {
+        self.nodes.get(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Index of the node living at `path`, if it is a child of this directory.
+    pub fn position(&self, path: &Path) -> Option<usize> {
+        self.nodes.iter().position(|n| n.root_path.as_path() == path)
+    }
+
     pub fn walk(&mut self) -> Vec<[String; 4]> {
+        let tree: bool = self.tree_depth.is_some();
         self.nodes.iter().map(
             |n| [
-                n.name().to_string_lossy().to_string(), 
+                match (tree, n.is_dir, n.expanded) {
+                    (false, _, _) => n.name().to_string_lossy().to_string(),
+                    (true, is_dir, expanded) => format!("{}{} {}", "  ".repeat(n.depth),
+                        if !is_dir { " " } else if expanded { "▾" } else { "▸" },
+                        n.name().to_string_lossy()),
+                },
                 n.modified.as_ref().unwrap().to_owned(), 
                 n.extension.as_str().to_owned(), 
                 human_size(n.size)
@@ -171,6 +322,190 @@ impl Directory {
 }
 
 
+/// The `.gitignore` rules of a directory and of its ancestors inside a walk, innermost last.
+#[derive(Clone, Default)]
+struct Ignores(Vec<Arc<Gitignore>>);
+
+impl Ignores {
+    /// The rules that apply inside `dir`.
+    fn enter(&self, dir: &Path) -> Ignores {
+        let mut builder = GitignoreBuilder::new(dir);
+        let found = dir.join(".gitignore");
+        let mut ignores = self.clone();
+        if found.is_file() && builder.add(found).is_none() {
+            if let Ok(gitignore) = builder.build() {
+                ignores.0.push(Arc::new(gitignore));
+            }
+        }
+        ignores
+    }
+
+    /// The innermost rule that mentions `path` wins, `!` rules included.
+    fn ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for gitignore in self.0.iter().rev() {
+            match gitignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {},
+            }
+        }
+        false
+    }
+}
+
+// More walkers than this mostly wait on the same disk.
+const MAX_WALKERS: usize = 8;
+
+/// How `walk` goes through a tree.
+#[derive(Clone, Copy)]
+pub(crate) struct WalkOptions {
+    /// Skip `.git` folders and whatever `.gitignore` files exclude.
+    pub gitignore: bool,
+    /// Children of the root are at depth 1, `None` walks all the way down.
+    pub max_depth: Option<usize>,
+}
+
+struct WalkTask {
+    dir: PathBuf,
+    ignores: Ignores,
+    depth: usize,
+}
+
+/// The folder holding the `.git` of the repository `dir` is in.
+fn repository(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|a| a.join(".git").exists())
+}
+
+/// Visits everything below `root` from several threads, in no particular order.
+/// Idle threads steal folders from busy ones, symlinked folders are not followed.
+/// The walk stops as soon as any `visit` returns false, which is how callers cancel it.
+/// Inside a repository the `.gitignore` files above `root` apply as well, as they do for git.
+pub(crate) fn walk(root: &Path, options: WalkOptions, visit: &(dyn Fn(&fs::DirEntry) -> bool + Sync)) {
+    walk_inside(root, root, options, visit)
+}
+
+/// Same as `walk` for `dir`, a folder below `root` that still obeys the `.gitignore` files in between.
+pub(crate) fn walk_inside(root: &Path, dir: &Path, options: WalkOptions, visit: &(dyn Fn(&fs::DirEntry) -> bool + Sync)) {
+    let mut ignores = Ignores::default();
+    if options.gitignore {
+        let repository: Option<&Path> = repository(dir);
+        let between: Vec<&Path> = dir.ancestors().skip(1)
+            .take_while(|a| a.starts_with(root) || repository.is_some_and(|r| a.starts_with(r)))
+            .collect();
+        for ancestor in between.into_iter().rev() {
+            ignores = ignores.enter(ancestor);
+        }
+    }
+    let threads: usize = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(MAX_WALKERS);
+    let injector: Injector<WalkTask> = Injector::new();
+    let workers: Vec<Worker<WalkTask>> = (0..threads).map(|_| Worker::new_lifo()).collect();
+    let stealers: Vec<Stealer<WalkTask>> = workers.iter().map(|w| w.stealer()).collect();
+    // Folders queued or being read, the walk is over once nothing is left.
+    let pending = AtomicUsize::new(1);
+    let stopped = AtomicBool::new(false);
+    injector.push(WalkTask { dir: dir.to_path_buf(), ignores, depth: 1 });
+
+    thread::scope(|scope| {
+        for local in workers {
+            let (injector, stealers, pending, stopped) = (&injector, &stealers, &pending, &stopped);
+            scope.spawn(move || {
+                let mut idle_rounds: u32 = 0;
+                while !stopped.load(Ordering::Relaxed) {
+                    match local.pop().or_else(|| steal(&local, injector, stealers)) {
+                        Some(task) => {
+                            idle_rounds = 0;
+                            if !walk_dir(task, options, &local, pending, visit) {
+                                stopped.store(true, Ordering::Relaxed);
+                            }
+                            pending.fetch_sub(1, Ordering::AcqRel);
+                        },
+                        None if pending.load(Ordering::Acquire) == 0 => break,
+                        None => {
+                            back_off(idle_rounds);
+                            idle_rounds = idle_rounds.saturating_add(1);
+                        },
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Reads everything up to `depth` levels below `dir` with `walk`, each folder right before what is inside it.
+/// `None` once more than `max_rows` turned up or `keep_going`, given the count so far, asks to stop.
+pub(crate) fn read_tree(dir: &Path, depth: usize, max_rows: usize, keep_going: &(dyn Fn(usize) -> bool + Sync)) -> Option<Vec<Node>> {
+    let nodes: Mutex<Vec<Node>> = Mutex::new(Vec::new());
+    let complete = AtomicBool::new(true);
+    let whole = WalkOptions { gitignore: false, max_depth: Some(depth) };
+    walk(dir, whole, &|entry| {
+        let path: PathBuf = entry.path();
+        let level: usize = path.strip_prefix(dir).map_or(1, |p| p.components().count());
+        // Symlinked folders are not walked into, they stay collapsed like any folder left unread.
+        let expanded: bool = entry.file_type().is_ok_and(|t| t.is_dir()) && level < depth;
+        let node = Node { depth: level - 1, expanded, ..Node::from(Arc::new(path)) };
+        let count: usize = {
+            let mut nodes = nodes.lock().unwrap();
+            nodes.push(node);
+            nodes.len()
+        };
+        let going: bool = count <= max_rows && keep_going(count);
+        if !going {
+            complete.store(false, Ordering::Relaxed);
+        }
+        going
+    });
+    if !complete.into_inner() {
+        return None;
+    }
+    let mut nodes: Vec<Node> = nodes.into_inner().unwrap();
+    // Paths compare component by component, which puts every folder right before its contents.
+    nodes.sort_by(|a, b| a.root_path.cmp(&b.root_path));
+    Some(nodes)
+}
+
+/// Waits a little longer every time a walker finds nothing to do, instead of spinning.
+fn back_off(idle_rounds: u32) {
+    match idle_rounds {
+        0..=15 => thread::yield_now(),
+        rounds => thread::sleep(Duration::from_micros(50 << (rounds - 16).min(5))),
+    }
+}
+
+fn steal(local: &Worker<WalkTask>, injector: &Injector<WalkTask>, stealers: &[Stealer<WalkTask>]) -> Option<WalkTask> {
+    std::iter::repeat_with(|| {
+        injector.steal_batch_and_pop(local).or_else(|| stealers.iter().map(|s| s.steal()).collect())
+    }).find(|s| !s.is_retry()).and_then(|s| s.success())
+}
+
+/// Visits the children of one folder and queues its subfolders, false once `visit` asked to stop.
+fn walk_dir(task: WalkTask, options: WalkOptions, local: &Worker<WalkTask>, pending: &AtomicUsize,
+            visit: &(dyn Fn(&fs::DirEntry) -> bool + Sync)) -> bool {
+    let ignores: Ignores = match options.gitignore {
+        true => task.ignores.enter(&task.dir),
+        false => task.ignores,
+    };
+    let entries = match fs::read_dir(&task.dir) {
+        Ok(entries) => entries,
+        // Unreadable folders are skipped, the rest is still worth walking.
+        Err(_) => return true,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let is_dir: bool = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if options.gitignore && (entry.file_name() == ".git" || ignores.ignored(&entry.path(), is_dir)) {
+            continue;
+        }
+        if !visit(&entry) {
+            return false;
+        }
+        if is_dir && options.max_depth.is_none_or(|max| task.depth < max) {
+            pending.fetch_add(1, Ordering::AcqRel);
+            local.push(WalkTask { dir: entry.path(), ignores: ignores.clone(), depth: task.depth + 1 });
+        }
+    }
+    true
+}
+
+
 /*
 // File Access Rights - docs: https://learn.microsoft.com/en-us/windows/win32/fileio/file-access-rights-constants.
 pub enum FCR {
@@ -0,0 +1,85 @@
+use std::thread;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub type JobResult = Result<String, String>;
+
+enum JobEvent {
+    Progress { done: u64, total: u64 },
+    Finished(JobResult),
+}
+
+/// Handed to the work running in the background.
+pub struct Reporter {
+    sender: Sender<JobEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Reporter {
+    pub fn progress(&self, done: u64, total: u64) {
+        let _ = self.sender.send(JobEvent::Progress { done, total });
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Long running work (moves, copies, searches...) the UI keeps drawing around.
+pub struct Job {
+    pub label: String,
+    pub progress: Option<(u64, u64)>,
+    receiver: Receiver<JobEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn spawn<F>(label: String, work: F) -> Self
+    where
+        F: FnOnce(&Reporter) -> JobResult + Send + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let reporter = Reporter {
+            sender,
+            cancelled: Arc::clone(&cancelled),
+        };
+        thread::spawn(move || {
+            let result = work(&reporter);
+            let _ = reporter.sender.send(JobEvent::Finished(result));
+        });
+
+        Self {
+            label,
+            progress: None,
+            receiver,
+            cancelled,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Drains pending progress, returns the outcome once the work is done.
+    pub fn poll(&mut self) -> Option<JobResult> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(JobEvent::Progress { done, total }) => self.progress = Some((done, total)),
+                Ok(JobEvent::Finished(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(format!("{} stopped unexpectedly", self.label))),
+            }
+        }
+    }
+
+    pub fn status(&self) -> String {
+        match self.progress {
+            Some((done, total)) if total > 0 => format!("{} {}%", self.label, done * 100 / total),
+            // Walks cannot know their total upfront, they count what they went through.
+            Some((done, _)) => format!("{}... {done} so far", self.label),
+            _ => format!("{}...", self.label),
+        }
+    }
+}
@@ -1,9 +1,22 @@
 mod ui;
 mod app;
 mod config;
+mod command;
 mod internal;
+mod fileops;
+mod clipboard;
+mod job;
+mod preview;
+mod search;
+mod index;
+mod opener;
+mod highlight;
+mod platform;
+mod archive;
 
 use std::io;
+use std::process::{self, ExitStatus};
+use std::time::Duration;
 use ratatui::prelude::*;
 use crossterm::{
     execute,
@@ -17,19 +30,51 @@ use app::{
     CursorDirection
 };
 
+// How long to wait for a key before drawing again, keeps job progress moving.
+const TICK: Duration = Duration::from_millis(100);
+
 fn handle_events(explorer: &mut App) -> Result<bool, io::Error> {
+    if !event::poll(TICK)? {
+        return Ok(false);
+    }
     if let Event::Key(key) = event::read()? {
+        if explorer.prompt.is_some() {
+            match key.code {
+                KeyCode::Char(answer) => explorer.answer_prompt(answer),
+                KeyCode::Esc => explorer.cancel_prompt(),
+                _ => {},
+            }
+            return Ok(false);
+        }
         match explorer.mode {
             Mode::Ended => { 
                 return Ok(true)
             },
             Mode::Normal => match key.code {
-                    KeyCode::Char(':') => explorer.mode = Mode::InsideShell,
+                    KeyCode::Char(':') => explorer.open_shell(),
                     KeyCode::Char('o') => explorer.mode = Mode::InsideOptions,
                     KeyCode::Char('f') => explorer.shift_down(),
                     KeyCode::Char('g') => explorer.shift_up(),
-                    KeyCode::Char('j') => {}, // TODO: Display tree of selected child. [inner]
-                    KeyCode::Char('h') => {}, // TODO: Return back into parent. [outer]
+                    KeyCode::Char('j') | KeyCode::Enter => explorer.descend(),
+                    KeyCode::Char('h') => explorer.ascend(),
+                    KeyCode::Char('t') => explorer.open_trash(),
+                    KeyCode::Char('s') => explorer.open_search(),
+                    KeyCode::Char('/') => explorer.open_finder(),
+                    KeyCode::Tab => explorer.toggle_folder(),
+                    KeyCode::Char('v') => explorer.open_viewer(),
+                    KeyCode::Char('r') => explorer.start_rename(),
+                    KeyCode::Char(' ') => explorer.toggle_mark(),
+                    KeyCode::Char('u') => explorer.clear_marks(),
+                    KeyCode::Char('V') => explorer.start_visual(),
+                    KeyCode::Char('x') => explorer.toggle_hex(),
+                    KeyCode::Char('y') => explorer.yank_selected(false),
+                    KeyCode::Char('c') => explorer.yank_selected(true),
+                    KeyCode::Char('p') => explorer.paste(),
+                    KeyCode::Char(']') => explorer.scroll_hex(1),
+                    KeyCode::Char('[') => explorer.scroll_hex(-1),
+                    KeyCode::PageDown => explorer.scroll_hex_page(true),
+                    KeyCode::PageUp => explorer.scroll_hex_page(false),
+                    KeyCode::Esc => explorer.cancel_job(),
                     _ => {}, 
             },
             Mode::InsideShell => {
@@ -40,16 +85,80 @@ fn handle_events(explorer: &mut App) -> Result<bool, io::Error> {
                         KeyCode::Backspace => explorer.delete_from_shell(),
                         KeyCode::Right => explorer.move_cursor(CursorDirection::Right),
                         KeyCode::Left => explorer.move_cursor(CursorDirection::Left),
-                        KeyCode::Esc => {
-                            explorer.mode = Mode::Normal;
-                            explorer.reset_shell();
-                        },
+                        KeyCode::Esc => explorer.close_shell(),
                         _ => {},
                     }
                 }
             },
+            Mode::InsideRename => match key.code {
+                    KeyCode::Enter => explorer.finish_rename(),
+                    KeyCode::Char(pressed) => explorer.rename_type(pressed),
+                    KeyCode::Backspace => explorer.rename_erase(),
+                    KeyCode::Right => explorer.rename_cursor(CursorDirection::Right),
+                    KeyCode::Left => explorer.rename_cursor(CursorDirection::Left),
+                    KeyCode::Esc => explorer.cancel_rename(),
+                    _ => {},
+            },
+            Mode::InsideVisual => match key.code {
+                    KeyCode::Char('f') => explorer.shift_down(),
+                    KeyCode::Char('g') => explorer.shift_up(),
+                    KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Char('V') => explorer.finish_visual(),
+                    KeyCode::Esc => explorer.cancel_visual(),
+                    _ => {},
+            },
+            Mode::InsideTrash => match key.code {
+                    KeyCode::Char(':') => explorer.open_shell(),
+                    KeyCode::Char('f') => explorer.shift_trash(true),
+                    KeyCode::Char('g') => explorer.shift_trash(false),
+                    KeyCode::Char('r') => explorer.restore_trashed(),
+                    KeyCode::Char('d') => explorer.ask_purge(),
+                    KeyCode::Char('E') => explorer.ask_empty_trash(),
+                    KeyCode::Esc => explorer.close_trash(),
+                    _ => {},
+            },
+            Mode::InsideSearch => match key.code {
+                    KeyCode::Char(':') => explorer.open_shell(),
+                    KeyCode::Char('f') => explorer.shift_search(true),
+                    KeyCode::Char('g') => explorer.shift_search(false),
+                    KeyCode::Enter => explorer.jump_to_result(),
+                    KeyCode::Esc => explorer.close_search(),
+                    _ => {},
+            },
+            Mode::InsideViewer if explorer.viewer_typing() => match key.code {
+                    KeyCode::Char(c) => explorer.viewer_type(c),
+                    KeyCode::Backspace => explorer.viewer_erase(),
+                    KeyCode::Enter => explorer.viewer_find(),
+                    KeyCode::Esc => explorer.viewer_cancel_search(),
+                    _ => {},
+            },
+            Mode::InsideViewer => match key.code {
+                    KeyCode::Char(':') => explorer.open_shell(),
+                    KeyCode::Char('f') | KeyCode::Down => explorer.scroll_viewer(1),
+                    KeyCode::Char('g') | KeyCode::Up => explorer.scroll_viewer(-1),
+                    KeyCode::PageDown | KeyCode::Char(' ') => explorer.scroll_viewer_page(true),
+                    KeyCode::PageUp => explorer.scroll_viewer_page(false),
+                    KeyCode::Home => explorer.viewer_edge(false),
+                    KeyCode::End => explorer.viewer_edge(true),
+                    KeyCode::Left => explorer.side_scroll_viewer(false),
+                    KeyCode::Right => explorer.side_scroll_viewer(true),
+                    KeyCode::Char('w') => explorer.toggle_wrap(),
+                    KeyCode::Char('/') => explorer.viewer_search(),
+                    KeyCode::Char('n') => explorer.viewer_next_match(true),
+                    KeyCode::Char('N') => explorer.viewer_next_match(false),
+                    KeyCode::Esc => explorer.close_viewer(),
+                    _ => {},
+            },
+            Mode::InsideFinder => match key.code {
+                    KeyCode::Char(c) => explorer.finder_type(c),
+                    KeyCode::Backspace => explorer.finder_erase(),
+                    KeyCode::Down => explorer.shift_finder(true),
+                    KeyCode::Up => explorer.shift_finder(false),
+                    KeyCode::Enter => explorer.choose_found(),
+                    KeyCode::Esc => explorer.close_finder(),
+                    _ => {},
+            },
             Mode::InsideOptions => match key.code {
-                    KeyCode::Char(':') => explorer.mode = Mode::InsideShell,
+                    KeyCode::Char(':') => explorer.open_shell(),
                     KeyCode::Esc => explorer.mode = Mode::Normal,
                     _ => {}, 
                 },
@@ -60,7 +169,29 @@ fn handle_events(explorer: &mut App) -> Result<bool, io::Error> {
 
 // TODO: Make options height fit the actual content.
 
+/// Hands the terminal over to `command` until it exits, then takes it back.
+fn run_in_foreground(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, mut command: process::Command) -> io::Result<ExitStatus> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    let status = command.status();
+
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    status
+}
+
 fn main() -> Result<(), io::Error> {
+    let mut explorer: App = match App::new() {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("owl: {e}");
+            std::process::exit(1);
+        }
+    };
+
     enable_raw_mode()?;
     let mut stdout: io::Stdout = io::stdout();
     stdout.execute(EnterAlternateScreen)?;
@@ -68,11 +199,15 @@ fn main() -> Result<(), io::Error> {
     let mut terminal: Terminal<CrosstermBackend<io::Stdout>> = Terminal::new(backend)?;
 
     let mut should_quit: bool = false;
-    let mut explorer: App = App::new().unwrap(); // TODO: HANDLE DRIVELOADINGERROR.
 
     while !should_quit {
+        explorer.tick();
         terminal.draw(|f: &mut Frame<'_>| ui::user_interface(f, &mut explorer))?;
-        should_quit = handle_events(&mut explorer).unwrap();
+        should_quit = handle_events(&mut explorer)? || matches!(explorer.mode, Mode::Ended);
+        if let Some(command) = explorer.foreground.take() {
+            let outcome = run_in_foreground(&mut terminal, command);
+            explorer.foreground_done(outcome);
+        }
     }
 
     disable_raw_mode()?;
@@ -0,0 +1,128 @@
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use crate::command;
+use crate::config::ConfigOpeners;
+use crate::preview::{self, Decoded};
+
+// Enough to tell the usual files apart, anything else is sniffed as text or binary.
+const MIME_TYPES: [(&str, &str); 36] = [
+    ("txt", "text/plain"), ("md", "text/markdown"), ("csv", "text/csv"), ("html", "text/html"),
+    ("htm", "text/html"), ("css", "text/css"), ("js", "text/javascript"), ("rs", "text/x-rust"),
+    ("py", "text/x-python"), ("c", "text/x-c"), ("h", "text/x-c"), ("cpp", "text/x-c++"),
+    ("java", "text/x-java"), ("go", "text/x-go"), ("sh", "application/x-sh"), ("json", "application/json"),
+    ("toml", "application/toml"), ("xml", "application/xml"), ("yaml", "application/yaml"), ("yml", "application/yaml"),
+    ("pdf", "application/pdf"), ("zip", "application/zip"), ("gz", "application/gzip"), ("tar", "application/x-tar"),
+    ("png", "image/png"), ("jpg", "image/jpeg"), ("jpeg", "image/jpeg"), ("gif", "image/gif"),
+    ("svg", "image/svg+xml"), ("webp", "image/webp"), ("mp3", "audio/mpeg"), ("wav", "audio/wav"),
+    ("flac", "audio/flac"), ("mp4", "video/mp4"), ("mkv", "video/x-matroska"), ("webm", "video/webm"),
+];
+
+/// How a file gets opened.
+pub enum Opener {
+    /// Takes over the terminal until it exits.
+    Terminal(Command),
+    /// Runs on its own, Owl keeps going.
+    Gui(Command),
+}
+
+/// The MIME type of `path`, from its extension or else from what it holds.
+pub(crate) fn mime_type(path: &Path) -> String {
+    if path.is_dir() {
+        return "inode/directory".to_string();
+    }
+    let extension: String = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if let Some((_, mime)) = MIME_TYPES.iter().find(|(ext, _)| *ext == extension) {
+        return mime.to_string();
+    }
+    match is_text(path) {
+        true => "text/plain".to_string(),
+        false => "application/octet-stream".to_string(),
+    }
+}
+
+/// Whether the head of `path` reads as text.
+pub(crate) fn is_text(path: &Path) -> bool {
+    matches!(preview::sniff(path).map(|head| preview::decode(&head)), Ok(Decoded::Text(..)))
+}
+
+/// The opener configured for `path`, if any.
+/// The extension wins over the exact MIME type, which wins over `type/*`.
+/// Rules needing a variable that is not set are passed over, like `$EDITOR` on a fresh machine.
+pub(crate) fn find(openers: &ConfigOpeners, path: &Path) -> io::Result<Option<Opener>> {
+    let extension: Option<String> = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let mime: String = mime_type(path);
+    let family: String = format!("{}/*", mime.split('/').next().unwrap_or_default());
+    let keys = extension.into_iter().chain([mime, family]);
+
+    for key in keys {
+        let rules = [(openers.terminal.get(&key), true), (openers.gui.get(&key), false)];
+        for (template, terminal) in rules.into_iter().filter_map(|(t, terminal)| Some((t?, terminal))) {
+            match command(template, path) {
+                Ok(command) if terminal => return Ok(Some(Opener::Terminal(command))),
+                Ok(command) => return Ok(Some(Opener::Gui(command))),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// `$EDITOR` opening `path`, for files Owl hands over to be edited.
+pub(crate) fn editor(path: &Path) -> io::Result<Command> {
+    command("$EDITOR {}", path)
+}
+
+/// Builds the command of an opener rule, `{}` stands for the file and is appended when missing.
+/// A word that is only `$VAR` may hold several words, like `EDITOR="code -w"`, an unset one is `NotFound`.
+fn command(template: &str, path: &Path) -> io::Result<Command> {
+    let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, format!("'{template}' {reason}"));
+    let unset = |var: &str| io::Error::new(io::ErrorKind::NotFound, format!("'{template}' needs ${var}, which is not set"));
+    let words: Vec<String> = command::tokenize(template).map_err(|e| invalid(e.to_string()))?;
+    let mut expanded: Vec<String> = Vec::new();
+    for word in words {
+        match word.strip_prefix('$').filter(|var| !var.is_empty() && var.chars().all(|c| c.is_alphanumeric() || c == '_')) {
+            Some(var) => {
+                let value: String = env::var(var).ok().filter(|v| !v.trim().is_empty())
+                    .ok_or_else(|| unset(var))?;
+                expanded.extend(value.split_whitespace().map(String::from));
+            },
+            None => expanded.push(word),
+        }
+    }
+    if !expanded.iter().any(|w| w.contains("{}")) {
+        expanded.push("{}".to_string());
+    }
+    let file: String = path.to_string_lossy().into_owned();
+    let mut words = expanded.into_iter().map(|w| w.replace("{}", &file));
+    let program: String = words.next().ok_or_else(|| invalid("is empty".to_string()))?;
+    let mut command = Command::new(program);
+    command.args(words);
+    Ok(command)
+}
+
+/// Starts `command` apart from Owl, so quitting or Ctrl+C in Owl leaves it running.
+pub(crate) fn spawn_detached(mut command: Command) -> io::Result<()> {
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    detach(&mut command);
+    let mut child = command.spawn()?;
+    // Reap it once it exits so it does not linger as a zombie.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    command.creation_flags(DETACHED_PROCESS);
+}
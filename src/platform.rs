@@ -0,0 +1,111 @@
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+mod xdg;
+#[cfg(windows)]
+mod windows;
+
+use std::io::{self, Write};
+use std::fmt;
+use std::thread;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Debug)]
+pub enum PlatformError {
+    Io(io::Error),
+    NoDrives,
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlatformError::Io(e) => write!(f, "{e}"),
+            PlatformError::NoDrives => write!(f, "no drives or mount points were found"),
+            PlatformError::Unsupported(what) => write!(f, "{what} is not supported on this platform"),
+        }
+    }
+}
+
+impl Error for PlatformError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PlatformError::Io(ref e) => Some(e),
+            PlatformError::NoDrives | PlatformError::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for PlatformError {
+    fn from(e: io::Error) -> Self {
+        PlatformError::Io(e)
+    }
+}
+
+pub type PlatformResult<T> = Result<T, PlatformError>;
+
+/// Something that was moved to the trash by `del`.
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    pub name: String,
+    pub original: PathBuf,
+    pub deleted: String,
+    pub location: PathBuf,
+}
+
+/// Everything Owl needs from the host operating system.
+pub trait Platform {
+    /// All drives (Windows) or mount points (Unix) available to browse.
+    fn drives(&self) -> PlatformResult<Vec<PathBuf>>;
+
+    /// The directory Owl starts in.
+    fn home(&self) -> PlatformResult<PathBuf>;
+
+    /// Hands `path` to whatever the desktop associates with it, without waiting.
+    fn open(&self, path: &Path) -> PlatformResult<()>;
+
+    /// Moves `path` to the recycle bin (Windows) or the XDG trash (Unix).
+    fn trash(&self, path: &Path) -> PlatformResult<()>;
+
+    /// Everything inside the trash, most recently deleted first.
+    fn trashed(&self) -> PlatformResult<Vec<TrashItem>>;
+
+    /// Moves `item` back to where it was deleted from.
+    fn restore(&self, item: &TrashItem) -> PlatformResult<()>;
+
+    /// Deletes `item` from the trash for good.
+    fn purge(&self, item: &TrashItem) -> PlatformResult<()>;
+
+    fn empty_trash(&self) -> PlatformResult<()>;
+
+    /// Places `text` on the system clipboard through the desktop's own tools.
+    fn clipboard(&self, text: &str) -> PlatformResult<()>;
+}
+
+/// Runs `tool` with `input` as its input, without waiting for it to exit.
+fn feed(tool: &[&str], input: &[u8]) -> io::Result<()> {
+    let mut child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    // Reap the tool once it exits so it does not linger as a zombie.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn native() -> impl Platform {
+    unix::Unix
+}
+
+#[cfg(windows)]
+pub fn native() -> impl Platform {
+    windows::Windows
+}
@@ -0,0 +1,131 @@
+use std::env;
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use super::{feed, xdg, Platform, PlatformError, PlatformResult, TrashItem};
+
+const MOUNTS: &str = "/proc/self/mounts";
+
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const OPENER: &str = "xdg-open";
+
+// Kernel filesystems nobody wants to browse.
+const PSEUDO_FILESYSTEMS: [&str; 17] = [
+    "proc", "sysfs", "devtmpfs", "devpts", "cgroup", "cgroup2", "securityfs", "pstore",
+    "debugfs", "tracefs", "configfs", "mqueue", "hugetlbfs", "fusectl", "bpf", "autofs", "binfmt_misc"
+];
+
+pub struct Unix;
+
+/// Mount points escape whitespace as octal, e.g. `\040` for a space.
+fn unescape_mount_point(raw: &str) -> String {
+    let bytes = raw.as_bytes();
+    let mut unescaped: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let octal = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("0");
+            unescaped.push(u8::from_str_radix(octal, 8).unwrap_or(b'?'));
+            i += 4;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&unescaped).to_string()
+}
+
+/// Clipboard tools worth trying, in order, for the running desktop session.
+fn clipboard_tools() -> Vec<&'static [&'static str]> {
+    let mut tools: Vec<&'static [&'static str]> = Vec::new();
+    if cfg!(target_os = "macos") {
+        tools.push(&["pbcopy"]);
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(&["wl-copy"]);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        tools.push(&["xclip", "-selection", "clipboard"]);
+        tools.push(&["xsel", "--clipboard", "--input"]);
+    }
+    tools
+}
+
+impl Platform for Unix {
+    fn drives(&self) -> PlatformResult<Vec<PathBuf>> {
+        let mounts = match fs::read_to_string(MOUNTS) {
+            Ok(mounts) => mounts,
+            // Not every Unix has procfs, the root is always there.
+            Err(_) => return Ok(vec![PathBuf::from("/")]),
+        };
+        let mut mount_points: Vec<PathBuf> = mounts.lines().filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_device, mount_point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            if PSEUDO_FILESYSTEMS.contains(&fs_type) {
+                return None;
+            }
+            Some(PathBuf::from(unescape_mount_point(mount_point)))
+        }).collect();
+        mount_points.sort();
+        mount_points.dedup();
+
+        if mount_points.is_empty() {
+            return Err(PlatformError::NoDrives);
+        }
+        Ok(mount_points)
+    }
+
+    fn home(&self) -> PlatformResult<PathBuf> {
+        match env::var_os("HOME").map(PathBuf::from) {
+            Some(home) if home.is_dir() => Ok(home),
+            _ => Ok(env::current_dir()?),
+        }
+    }
+
+    fn open(&self, path: &Path) -> PlatformResult<()> {
+        let mut child = Command::new(OPENER)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("{OPENER} failed, {e}")))?;
+        // Reap the opener once it exits so it does not linger as a zombie.
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    fn trash(&self, path: &Path) -> PlatformResult<()> {
+        Ok(xdg::trash(path)?)
+    }
+
+    fn trashed(&self) -> PlatformResult<Vec<TrashItem>> {
+        Ok(xdg::trashed(&self.drives().unwrap_or_default())?)
+    }
+
+    fn restore(&self, item: &TrashItem) -> PlatformResult<()> {
+        Ok(xdg::restore(item)?)
+    }
+
+    fn purge(&self, item: &TrashItem) -> PlatformResult<()> {
+        Ok(xdg::purge(item)?)
+    }
+
+    fn empty_trash(&self) -> PlatformResult<()> {
+        Ok(xdg::empty(&self.drives().unwrap_or_default())?)
+    }
+
+    fn clipboard(&self, text: &str) -> PlatformResult<()> {
+        for tool in clipboard_tools() {
+            match feed(tool, text.as_bytes()) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                fed => return Ok(fed?),
+            }
+        }
+        Err(PlatformError::Unsupported("the system clipboard"))
+    }
+}
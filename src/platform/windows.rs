@@ -0,0 +1,94 @@
+use std::ffi::c_void;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use winsafe::{self as w, co::{self, ERROR}};
+use super::{feed, Platform, PlatformError, PlatformResult, TrashItem};
+
+pub struct Windows;
+
+#[link(name = "shell32")]
+extern "system" {
+    fn SHFileOperationW(operation: *mut c_void) -> i32;
+}
+
+fn os_error(e: ERROR) -> PlatformError {
+    PlatformError::Io(io::Error::from_raw_os_error(e.raw() as i32))
+}
+
+impl Platform for Windows {
+    fn drives(&self) -> PlatformResult<Vec<PathBuf>> {
+        if w::GetLogicalDrives() == 0 {
+            return Err(os_error(w::GetLastError()));
+        }
+        let logical_drives: Vec<String> = w::GetLogicalDriveStrings().map_err(os_error)?;
+        Ok(logical_drives.into_iter().map(PathBuf::from).collect::<Vec<PathBuf>>())
+    }
+
+    fn home(&self) -> PlatformResult<PathBuf> {
+        let available_drives: Vec<PathBuf> = self.drives()?;
+        match available_drives.first() {
+            Some(home_drive) => Ok(home_drive.to_owned()),
+            None => Err(PlatformError::NoDrives),
+        }
+    }
+
+    fn open(&self, path: &Path) -> PlatformResult<()> {
+        // The empty string is the window title `start` expects before the target.
+        let mut child = Command::new("cmd")
+            .args(["/C", "start", ""])
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("start failed, {e}")))?;
+        // Reap the opener once it exits so it does not linger as a zombie.
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    fn trash(&self, path: &Path) -> PlatformResult<()> {
+        let mut from = w::WString::from_str_vec(&[path.to_string_lossy()]);
+        let mut operation = w::SHFILEOPSTRUCT::default();
+        operation.wFunc = co::FO::DELETE;
+        operation.fFlags = co::FOF::ALLOWUNDO | co::FOF::NO_UI;
+        operation.set_pFrom(Some(&mut from));
+        // winsafe takes the zero SHFileOperationW returns on success for a failure, so call it directly.
+        let code: i32 = unsafe { SHFileOperationW(&mut operation as *mut w::SHFILEOPSTRUCT as *mut c_void) };
+        if code != 0 {
+            return Err(PlatformError::Io(io::Error::from_raw_os_error(code)));
+        }
+        if operation.fAnyOperationsAborted() {
+            return Err(PlatformError::Io(io::Error::new(io::ErrorKind::Interrupted, "moving to the recycle bin was cancelled")));
+        }
+        Ok(())
+    }
+
+    // Browsing the recycle bin goes through the shell namespace, use Explorer for it.
+    fn trashed(&self) -> PlatformResult<Vec<TrashItem>> {
+        Err(PlatformError::Unsupported("browsing the recycle bin"))
+    }
+
+    fn restore(&self, _item: &TrashItem) -> PlatformResult<()> {
+        Err(PlatformError::Unsupported("restoring from the recycle bin"))
+    }
+
+    fn purge(&self, _item: &TrashItem) -> PlatformResult<()> {
+        Err(PlatformError::Unsupported("purging from the recycle bin"))
+    }
+
+    fn empty_trash(&self) -> PlatformResult<()> {
+        Err(PlatformError::Unsupported("emptying the recycle bin"))
+    }
+
+    // clip reads its input in the OEM code page unless it starts with a UTF-16 byte order mark.
+    fn clipboard(&self, text: &str) -> PlatformResult<()> {
+        let utf16: Vec<u8> = std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        Ok(feed(&["clip"], &utf16)?)
+    }
+}
@@ -0,0 +1,261 @@
+// Trash layout docs: https://specifications.freedesktop.org/trash-spec/trashspec-latest.html.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use chrono::{Local, NaiveDateTime};
+use crate::fileops;
+use super::TrashItem;
+
+const INFO_EXTENSION: &str = "trashinfo";
+
+/// `$XDG_DATA_HOME/Trash`, falling back to `~/.local/share/Trash`.
+fn home_trash() -> io::Result<PathBuf> {
+    let data_home: PathBuf = match env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(data_home) => PathBuf::from(data_home),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local/share"),
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "$HOME is not set")),
+        },
+    };
+    fs::create_dir_all(&data_home)?;
+    let trash = data_home.join("Trash");
+    for dir in [trash.join("files"), trash.join("info")] {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    Ok(trash)
+}
+
+/// The trash on the drive mounted at `topdir`, `$topdir/.Trash/$uid` when an administrator
+/// set up `.Trash` as a sticky folder and `$topdir/.Trash-$uid` otherwise.
+fn topdir_trash(topdir: &Path, uid: u32) -> PathBuf {
+    let shared = topdir.join(".Trash");
+    let sticky = shared.symlink_metadata().is_ok_and(|md| md.is_dir() && md.permissions().mode() & 0o1000 != 0);
+    match sticky {
+        true => shared.join(uid.to_string()),
+        false => topdir.join(format!(".Trash-{uid}")),
+    }
+}
+
+/// Creates the trash on another drive, refusing one that is a symlink or belongs to someone else.
+fn prepare_topdir_trash(trash: &Path, uid: u32) -> io::Result<()> {
+    for dir in [trash.join("files"), trash.join("info")] {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    match trash.symlink_metadata()? {
+        md if md.is_dir() && md.uid() == uid => Ok(()),
+        _ => Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is not a trash of yours", trash.display()))),
+    }
+}
+
+/// The outermost folder holding `path` on the same device, where that drive is mounted.
+fn topdir(path: &Path, dev: u64) -> &Path {
+    path.ancestors().skip(1)
+        .take_while(|a| fs::metadata(a).is_ok_and(|md| md.dev() == dev))
+        .last()
+        .unwrap_or(path)
+}
+
+/// The trash `path` goes to, the one on its own drive so trashing never copies anything.
+fn trash_of(path: &Path) -> io::Result<PathBuf> {
+    let home = home_trash()?;
+    let home_md = fs::metadata(&home)?;
+    let dev: u64 = path.symlink_metadata()?.dev();
+    if home_md.dev() == dev {
+        return Ok(home);
+    }
+    // The home trash is ours, so it tells whose trash to use on other drives.
+    let trash = topdir_trash(topdir(path, dev), home_md.uid());
+    prepare_topdir_trash(&trash, home_md.uid()).map_err(|e| io::Error::new(
+        e.kind(),
+        format!("the drive of {} has no trash ({e}), del --force deletes it for good", path.display())
+    ))?;
+    Ok(trash)
+}
+
+/// The home trash and the trash of every mount point that has one, with the folder
+/// relative paths inside them start from.
+fn all_trashes(mounts: &[PathBuf]) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let home = home_trash()?;
+    let uid: u32 = fs::metadata(&home)?.uid();
+    let mut trashes: Vec<(PathBuf, PathBuf)> = vec![(home.clone(), home.parent().unwrap_or(&home).to_path_buf())];
+    for mount in mounts {
+        let trash = topdir_trash(mount, uid);
+        let owned = trash.symlink_metadata().is_ok_and(|md| md.is_dir() && md.uid() == uid);
+        if owned && trash != home && trashes.iter().all(|(known, _)| *known != trash) {
+            trashes.push((trash, mount.to_owned()));
+        }
+    }
+    Ok(trashes)
+}
+
+fn percent_encode(path: &Path) -> String {
+    path.as_os_str().as_bytes().iter().map(|&b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+        _ => format!("%{b:02X}"),
+    }).collect()
+}
+
+fn percent_decode(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            },
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(OsString::from_vec(decoded))
+}
+
+/// Reserves a name inside the trash by creating its `.trashinfo` file.
+/// The `create_new` makes the reservation atomic, even between processes.
+fn reserve(trash: &Path, original: &Path) -> io::Result<(String, fs::File)> {
+    let name = original.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash a root"))?;
+    let mut attempt = 1;
+    loop {
+        let candidate = match attempt {
+            1 => name.to_owned(),
+            n => format!("{name}.{n}"),
+        };
+        let info = trash.join("info").join(format!("{candidate}.{INFO_EXTENSION}"));
+        if !trash.join("files").join(&candidate).exists() {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&info) {
+                Ok(file) => return Ok((candidate, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+                Err(e) => return Err(e),
+            }
+        }
+        attempt += 1;
+    }
+}
+
+pub(crate) fn trash(path: &Path) -> io::Result<()> {
+    let trash = trash_of(path)?;
+    let (name, mut info) = reserve(&trash, path)?;
+    let info_path = trash.join("info").join(format!("{name}.{INFO_EXTENSION}"));
+
+    let written = writeln!(
+        info,
+        "[Trash Info]\nPath={}\nDeletionDate={}",
+        percent_encode(path),
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    ).and_then(|_| fs::rename(path, trash.join("files").join(&name)));
+
+    if written.is_err() {
+        let _ = fs::remove_file(info_path);
+    }
+    written
+}
+
+/// Reads a `.trashinfo` file, returns the original path and deletion date.
+fn parse_info(info: &str) -> Option<(PathBuf, Option<NaiveDateTime>)> {
+    let mut lines = info.lines().map(str::trim).skip_while(|l| *l != "[Trash Info]").skip(1);
+    let (mut original, mut deleted) = (None, None);
+    for line in lines.by_ref().take_while(|l| !l.starts_with('[')) {
+        if let Some(path) = line.strip_prefix("Path=") {
+            original = Some(percent_decode(path));
+        } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+            deleted = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S").ok();
+        }
+    }
+    original.map(|o| (o, deleted))
+}
+
+/// Everything inside the home trash and the trashes of `mounts`, most recently deleted first.
+pub(crate) fn trashed(mounts: &[PathBuf]) -> io::Result<Vec<TrashItem>> {
+    let mut items: Vec<(Option<NaiveDateTime>, TrashItem)> = Vec::new();
+    for (trash, base) in all_trashes(mounts)? {
+        items_in(&trash, &base, &mut items)?;
+    }
+    items.sort_by_key(|item| std::cmp::Reverse(item.0));
+    Ok(items.into_iter().map(|(_, item)| item).collect())
+}
+
+/// Adds the items of one trash, whose relative paths start from `base`.
+fn items_in(trash: &Path, base: &Path, items: &mut Vec<(Option<NaiveDateTime>, TrashItem)>) -> io::Result<()> {
+    for entry in fs::read_dir(trash.join("info"))? {
+        let info_path = entry?.path();
+        if info_path.extension().and_then(|e| e.to_str()) != Some(INFO_EXTENSION) {
+            continue;
+        }
+        let name = match info_path.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let parsed = fs::read_to_string(&info_path).ok().and_then(|info| parse_info(&info));
+        let (original, deleted) = match parsed {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        // Trashes on other drives may store paths relative to where the drive is mounted.
+        let original = match original.is_absolute() {
+            true => original,
+            false => base.join(original),
+        };
+        items.push((deleted, TrashItem {
+            location: trash.join("files").join(&name),
+            name,
+            original,
+            deleted: deleted.map(|d| d.format("%d/%m/%Y %H:%M").to_string()).unwrap_or("Unknown".to_string()),
+        }));
+    }
+    Ok(())
+}
+
+/// The `.trashinfo` of `item`, in the trash holding it.
+fn info_of(item: &TrashItem) -> io::Result<PathBuf> {
+    let trash: &Path = item.location.parent().and_then(Path::parent)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not inside a trash", item.location.display())))?;
+    Ok(trash.join("info").join(format!("{}.{INFO_EXTENSION}", item.name)))
+}
+
+pub(crate) fn restore(item: &TrashItem) -> io::Result<()> {
+    if item.original.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", item.original.display())
+        ));
+    }
+    if let Some(parent) = item.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fileops::move_path(&item.location, &item.original)?;
+    fs::remove_file(info_of(item)?)
+}
+
+pub(crate) fn purge(item: &TrashItem) -> io::Result<()> {
+    match fileops::remove_tree(&item.location) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {},
+    }
+    fs::remove_file(info_of(item)?)
+}
+
+/// Empties the home trash and the trashes of `mounts`.
+pub(crate) fn empty(mounts: &[PathBuf]) -> io::Result<()> {
+    for (trash, _) in all_trashes(mounts)? {
+        for dir in ["files", "info"] {
+            for entry in fs::read_dir(trash.join(dir))? {
+                fileops::remove_tree(&entry?.path())?;
+            }
+        }
+    }
+    Ok(())
+}
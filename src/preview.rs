@@ -0,0 +1,178 @@
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use crate::internal::{human_permissions, human_size, Node};
+use crate::highlight::{self, Highlight, Syntax};
+use crate::config::ConfigPreview;
+
+// Only the head of a file is ever read, no matter how large it is.
+const SNIFF_BYTES: u64 = 64 * 1024;
+const TAB: &str = "    ";
+pub const HEX_ROW_BYTES: u64 = 16;
+
+pub enum Preview {
+    Text {
+        encoding: &'static str,
+        lines: Vec<String>,
+        highlights: Option<Vec<Vec<Highlight>>>,
+    },
+    Listing(Vec<String>),
+    Metadata(Vec<(&'static str, String)>),
+    /// A file that failed text detection, it can also be shown as a hex dump.
+    Binary(Vec<(&'static str, String)>),
+}
+
+pub struct HexRow {
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Reads `rows` rows of the dump starting at row `first`, and nothing else.
+pub(crate) fn hex_window(path: &Path, first: u64, rows: usize) -> io::Result<Vec<HexRow>> {
+    let mut file = fs::File::open(path)?;
+    let start = first * HEX_ROW_BYTES;
+    file.seek(SeekFrom::Start(start))?;
+    let mut window: Vec<u8> = Vec::new();
+    file.take(rows as u64 * HEX_ROW_BYTES).read_to_end(&mut window)?;
+
+    Ok(window.chunks(HEX_ROW_BYTES as usize).enumerate().map(|(i, chunk)| HexRow {
+        offset: start + i as u64 * HEX_ROW_BYTES,
+        bytes: chunk.to_vec(),
+    }).collect())
+}
+
+pub enum Decoded {
+    Text(&'static str, String),
+    Binary,
+}
+
+/// Decodes the head of a file, or reports it as binary.
+/// Byte order marks win, then UTF-8, then Latin-1 for mostly printable data.
+pub(crate) fn decode(bytes: &[u8]) -> Decoded {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return Decoded::Text("UTF-8 BOM", String::from_utf8_lossy(rest).to_string());
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return Decoded::Text("UTF-16LE", decode_utf16(rest, u16::from_le_bytes));
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return Decoded::Text("UTF-16BE", decode_utf16(rest, u16::from_be_bytes));
+    }
+    if bytes.contains(&0) {
+        return Decoded::Binary;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => return Decoded::Text("UTF-8", text.to_string()),
+        // A character cut in half by the sniffing window is still UTF-8.
+        Err(e) if e.error_len().is_none() => {
+            return Decoded::Text("UTF-8", String::from_utf8_lossy(&bytes[..e.valid_up_to()]).to_string());
+        },
+        Err(_) => {},
+    }
+    let control = bytes.iter()
+        .filter(|b| b.is_ascii_control() && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B))
+        .count();
+    if control * 10 > bytes.len() {
+        return Decoded::Binary;
+    }
+    Decoded::Text("ISO-8859-1", bytes.iter().map(|&b| b as char).collect())
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// `take` lines of a text file after skipping `skip`, nothing for binaries.
+pub(crate) fn text_window(path: &Path, skip: usize, take: usize) -> io::Result<Vec<String>> {
+    match decode(&fs::read(path)?) {
+        Decoded::Text(_, text) => Ok(text.lines().skip(skip).take(take).map(|l| l.replace('\t', TAB)).collect()),
+        Decoded::Binary => Ok(Vec::new()),
+    }
+}
+
+pub(crate) fn sniff(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head: Vec<u8> = Vec::new();
+    fs::File::open(path)?.take(SNIFF_BYTES).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+fn text_lines(text: &str, max_lines: usize) -> Vec<String> {
+    text.lines()
+        .take(max_lines)
+        .map(|l| l.replace('\t', TAB))
+        .collect()
+}
+
+fn listing(path: &Path, max_lines: usize) -> io::Result<Vec<String>> {
+    let mut children: Vec<String> = fs::read_dir(path)?
+        .filter_map(|rd| rd.ok())
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            match e.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                true => format!("{name}/"),
+                false => name,
+            }
+        })
+        .collect();
+    children.sort_by_key(|c| c.to_lowercase());
+
+    let total = children.len();
+    if total > max_lines {
+        children.truncate(max_lines);
+        children.push(format!("... and {} more", total - max_lines));
+    }
+    if children.is_empty() {
+        children.push("<empty>".to_string());
+    }
+    Ok(children)
+}
+
+pub(crate) fn metadata(node: &Node) -> Vec<(&'static str, String)> {
+    let resolved = |date: &io::Result<String>| match date {
+        Ok(d) => d.to_owned(),
+        Err(_) => "Unavailable".to_string(),
+    };
+    vec![
+        ("Name", node.name().to_string_lossy().to_string()),
+        ("Type", node.extension.to_owned()),
+        ("Size", human_size(node.size)),
+        ("Created", resolved(&node.created)),
+        ("Accessed", resolved(&node.accessed)),
+        ("Modified", resolved(&node.modified)),
+        ("Permissions", node.permissions.as_ref().map(human_permissions).unwrap_or("Unavailable".to_string())),
+    ]
+}
+
+impl Preview {
+    pub fn load(node: &Node, conf: &ConfigPreview) -> Self {
+        let path = node.root_path.as_path();
+        if node.is_dir {
+            return match listing(path, conf.max_lines) {
+                Ok(children) => Preview::Listing(children),
+                Err(_) => Preview::Metadata(metadata(node)),
+            };
+        }
+        if node.is_file {
+            let decoded = sniff(path).map(|head| decode(&head));
+            if let Ok(Decoded::Binary) = decoded {
+                return Preview::Binary(metadata(node));
+            }
+            if let Ok(Decoded::Text(encoding, text)) = decoded {
+                let lines = text_lines(&text, conf.max_lines);
+                // Large files are usually generated, plain text is good enough there.
+                let highlights = Syntax::from_extension(&node.extension)
+                    .filter(|_| node.size <= conf.highlight_max_size)
+                    .map(|syntax| highlight::highlight(syntax, &lines));
+                return Preview::Text {
+                    encoding,
+                    lines,
+                    highlights,
+                };
+            }
+        }
+        Preview::Metadata(metadata(node))
+    }
+}
@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+
+// Long lines are cut down to this much around the match.
+const SNIPPET_BEFORE: usize = 20;
+const SNIPPET_AFTER: usize = 160;
+const TAB: &str = "    ";
+
+/// A line of a file `grp` found something in, `start..end` is the match inside `snippet`.
+pub struct LineMatch {
+    pub number: usize,
+    pub snippet: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A search result, content searches also say where inside the file.
+pub struct Found {
+    pub path: PathBuf,
+    pub line: Option<LineMatch>,
+}
+
+/// Syntax errors span several lines pointing at the mistake, the last one says what it is.
+pub(crate) fn compile_regex(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| {
+        let message = e.to_string();
+        let reason = message.lines().last().unwrap_or_default().trim_start_matches("error: ").to_owned();
+        format!("is not a valid regex, {reason}")
+    })
+}
+
+fn floor_boundary(line: &str, mut at: usize) -> usize {
+    while !line.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+/// The first match of every line of `text` that has one.
+pub(crate) fn grep(text: &str, matcher: &Regex) -> Vec<LineMatch> {
+    text.lines().enumerate().filter_map(|(i, line)| {
+        let line: String = line.replace('\t', TAB);
+        let found = matcher.find(&line)?;
+        let from: usize = floor_boundary(&line, found.start().saturating_sub(SNIPPET_BEFORE));
+        let to: usize = floor_boundary(&line, (found.end() + SNIPPET_AFTER).min(line.len()));
+        Some(LineMatch {
+            number: i + 1,
+            snippet: line[from..to].to_owned(),
+            start: found.start() - from,
+            end: found.end().min(to) - from,
+        })
+    }).collect()
+}
+
+/// How `ser` decides whether a file name matches.
+pub enum Pattern {
+    Exact(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Globs are told apart by their wildcards, regexes are only used when asked for.
+    pub fn parse(pattern: &str, regex: bool) -> Result<Self, String> {
+        if regex {
+            return compile_regex(pattern).map(Pattern::Regex);
+        }
+        if pattern.contains(['*', '?', '[', '{']) {
+            return Glob::new(pattern)
+                .map(|glob| Pattern::Glob(glob.compile_matcher()))
+                .map_err(|e| format!("is not a valid glob, {}", e.kind()));
+        }
+        Ok(Pattern::Exact(pattern.to_string()))
+    }
+
+    /// What every matching name starts with, lowercased, `\0` terminated when the whole name is known.
+    pub fn prefix(&self) -> Option<String> {
+        let prefix: String = match self {
+            Pattern::Exact(exact) => format!("{}\0", exact.to_lowercase()),
+            Pattern::Glob(glob) => glob.glob().glob()
+                .chars()
+                .take_while(|c| !['*', '?', '[', '{', '\\'].contains(c))
+                .collect::<String>()
+                .to_lowercase(),
+            Pattern::Regex(_) => String::new(),
+        };
+        Some(prefix).filter(|p| !p.is_empty())
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => name == exact,
+            Pattern::Glob(glob) => glob.is_match(name),
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+// How the fuzzy finder weighs a match.
+const FUZZY_CONSECUTIVE: i64 = 6;
+const FUZZY_WORD_START: i64 = 8;
+const FUZZY_IN_NAME: i64 = 2;
+const FUZZY_MAX_GAP_PENALTY: i64 = 8;
+
+fn same_letter(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+fn word_start(chars: &[char], at: usize) -> bool {
+    at == 0 || matches!(chars[at - 1], '/' | '\\' | '_' | '-' | '.' | ' ')
+        || (chars[at - 1].is_lowercase() && chars[at].is_uppercase())
+}
+
+/// Scores `candidate` when every char of `query` appears in it in order, ignoring case.
+/// Also returns the char positions that matched, to highlight them.
+pub(crate) fn fuzzy(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    // The earliest place the whole query fits ends the window...
+    let mut q: usize = 0;
+    let mut end: usize = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        if q < query.len() && same_letter(c, query[q]) {
+            q += 1;
+            end = i;
+        }
+    }
+    if q < query.len() {
+        return None;
+    }
+    // ...and walking back from there makes it as tight as possible.
+    let mut positions: Vec<usize> = Vec::with_capacity(query.len());
+    let mut at: usize = end + 1;
+    for &wanted in query.iter().rev() {
+        at = (0..at).rev().find(|&i| same_letter(chars[i], wanted))?;
+        positions.push(at);
+    }
+    positions.reverse();
+
+    let name_start: usize = chars.iter().rposition(|&c| c == '/' || c == '\\').map_or(0, |i| i + 1);
+    let mut score: i64 = 0;
+    for (k, &pos) in positions.iter().enumerate() {
+        score += 1;
+        match k.checked_sub(1).map(|p| positions[p]) {
+            Some(previous) if previous + 1 == pos => score += FUZZY_CONSECUTIVE,
+            Some(previous) => score -= ((pos - previous - 1) as i64).min(FUZZY_MAX_GAP_PENALTY),
+            None => {},
+        }
+        if word_start(&chars, pos) {
+            score += FUZZY_WORD_START;
+        }
+        if pos >= name_start {
+            score += FUZZY_IN_NAME;
+        }
+    }
+    Some((score, positions))
+}
@@ -1,14 +1,20 @@
 use std::fmt;
 use std::num::ParseIntError;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
 use ratatui::{prelude::*, widgets::*};
-use crate::app::{App, Mode};
-use crate::config::Config;
+use crate::app::{App, AppFinder, AppShell, Feedback, Mode, Pending};
+use crate::config::{Config, SyntaxScheme};
 use crate::internal::Directory;
+use crate::preview::{HexRow, Preview};
+use crate::highlight::{Highlight, Token};
 
 pub struct ModeBar;
 pub struct Shell;
 pub struct Options;
+pub struct Finder;
+pub struct HexDump;
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
@@ -31,12 +37,79 @@ fn parse_to_color(s: &String) -> Result<Color, ParseError> {
     let parsed: Result<Vec<u8>, ParseIntError> = parts.into_iter().map(|val| val.trim().parse::<u8>()).collect();
     match parsed {
         Ok(p) => Ok(Color::Rgb(p[0], p[1], p[2])),
-        Err(_) => return Err(ParseError::ParseColorError(s.to_string()))
+        Err(_) => Err(ParseError::ParseColorError(s.to_string()))
+    }
+}
+
+/// Token styles, built from `[color_schemes.syntax]`.
+pub struct Theme {
+    keyword: Color,
+    string: Color,
+    number: Color,
+    comment: Color,
+    type_name: Color,
+    function: Color,
+    attribute: Color,
+    heading: Color,
+    link: Color,
+}
+
+impl Theme {
+    pub fn new(scheme: &SyntaxScheme) -> Self {
+        Self {
+            keyword: parse_to_color(&scheme.keyword).unwrap(),
+            string: parse_to_color(&scheme.string).unwrap(),
+            number: parse_to_color(&scheme.number).unwrap(),
+            comment: parse_to_color(&scheme.comment).unwrap(),
+            type_name: parse_to_color(&scheme.type_name).unwrap(),
+            function: parse_to_color(&scheme.function).unwrap(),
+            attribute: parse_to_color(&scheme.attribute).unwrap(),
+            heading: parse_to_color(&scheme.heading).unwrap(),
+            link: parse_to_color(&scheme.link).unwrap(),
+        }
+    }
+
+    pub fn style(&self, token: Token) -> Style {
+        let style = Style::default();
+        match token {
+            Token::Keyword => style.fg(self.keyword),
+            Token::String | Token::Code => style.fg(self.string),
+            Token::Number => style.fg(self.number),
+            Token::Comment => style.fg(self.comment).add_modifier(Modifier::ITALIC),
+            Token::Type => style.fg(self.type_name),
+            Token::Function | Token::Key => style.fg(self.function),
+            Token::Attribute => style.fg(self.attribute),
+            Token::Heading => style.fg(self.heading).add_modifier(Modifier::BOLD),
+            Token::Link => style.fg(self.link).add_modifier(Modifier::UNDERLINED),
+            Token::Emphasis => style.add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// Splits `line` into spans, leaving the gaps between highlights unstyled.
+    pub fn line<'a>(&self, line: &str, highlights: &[Highlight]) -> Line<'a> {
+        let mut spans: Vec<Span<'a>> = Vec::new();
+        let mut cursor = 0;
+        for h in highlights {
+            let inside = h.start >= cursor && h.start <= h.end && h.end <= line.len();
+            if !inside || !line.is_char_boundary(h.start) || !line.is_char_boundary(h.end) {
+                continue;
+            }
+            if h.start > cursor {
+                spans.push(Span::raw(line[cursor..h.start].to_owned()));
+            }
+            spans.push(Span::styled(line[h.start..h.end].to_owned(), self.style(h.token)));
+            cursor = h.end;
+        }
+        if cursor < line.len() {
+            spans.push(Span::raw(line[cursor..].to_owned()));
+        }
+        Line::from(spans)
     }
 }
 
 impl ModeBar {
-    pub fn new(mode: String, conf: &Config) -> Paragraph {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(mode: String, conf: &Config) -> Paragraph<'_> {
         let style: Style = Style::default()
             .fg(parse_to_color(&conf.color_schemes.bg).unwrap())
             .bg(parse_to_color(&conf.color_schemes.fg).unwrap());
@@ -49,12 +122,20 @@ impl ModeBar {
 }
 
 impl Shell {
-    pub fn new(input: String, conf: &Config) -> Paragraph {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<'a>(app_shell: &AppShell, question: Option<&str>, conf: &'a Config) -> Paragraph<'a> {
         let style: Style = Style::default()
             .fg(parse_to_color(&conf.color_schemes.fg).unwrap())
             .bg(parse_to_color(&conf.color_schemes.bg).unwrap());
         let block: Block<'_> = Block::default();
-        let shell: Paragraph<'_> = Paragraph::new(input)
+        let mut line: Vec<Span<'_>> = vec![Span::raw(app_shell.input.to_owned())];
+        match (question, &app_shell.feedback) {
+            (Some(q), _) => line = vec![Span::styled(q.to_owned(), Style::default().add_modifier(Modifier::BOLD))],
+            (None, Some(Feedback::Error(e))) => line.push(Span::styled(format!("  {e}"), Style::default().fg(Color::Red))),
+            (None, Some(Feedback::Info(i))) => line.push(Span::styled(format!("  {i}"), Style::default().add_modifier(Modifier::ITALIC))),
+            (None, None) => {}
+        }
+        let shell: Paragraph<'_> = Paragraph::new(Line::from(line))
             .style(style)
             .block(block)
             .wrap(Wrap { trim: true });
@@ -63,7 +144,8 @@ impl Shell {
 }
 
 impl Options {
-    pub fn new(title: String, conf: &Config) -> List {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(title: String, conf: &Config) -> List<'_> {
         let options_style: Style = Style::default()
             .fg(parse_to_color(&conf.color_schemes.fg).unwrap())
             .bg(parse_to_color(&conf.color_schemes.bg).unwrap());
@@ -71,7 +153,7 @@ impl Options {
             .title(title)
             .borders(Borders::ALL);
 
-        let items: Vec<ListItem> = (&conf.options.ops).into_iter().map(|op| {
+        let items: Vec<ListItem> = conf.options.ops.iter().map(|op| {
             ListItem::from(op.as_str())
         }).collect();
         let options: List<'_> = List::new(items)
@@ -84,6 +166,76 @@ impl Options {
     }
 }
 
+impl Finder {
+    /// Every fuzzy match with the matched chars picked out.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<'a>(finder: &AppFinder, conf: &Config) -> List<'a> {
+        let style: Style = Style::default()
+            .fg(parse_to_color(&conf.color_schemes.fg).unwrap())
+            .bg(parse_to_color(&conf.color_schemes.bg).unwrap());
+        let matched: Style = Style::default()
+            .fg(parse_to_color(&conf.color_schemes.syntax.keyword).unwrap())
+            .add_modifier(Modifier::BOLD);
+        let block: Block<'_> = Block::default()
+            .title(format!("Find > {}_", finder.query))
+            .borders(Borders::ALL);
+
+        let items: Vec<ListItem> = finder.matches.iter().map(|(idx, positions)| {
+            let spans: Vec<Span<'a>> = finder.paths[*idx].chars().enumerate()
+                .map(|(i, c)| match positions.binary_search(&i) {
+                    Ok(_) => Span::styled(c.to_string(), matched),
+                    Err(_) => Span::raw(c.to_string()),
+                })
+                .collect();
+            ListItem::new(Line::from(spans))
+        }).collect();
+        List::new(items)
+            .block(block)
+            .style(style)
+            .highlight_symbol("> ")
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_spacing(HighlightSpacing::Always)
+    }
+}
+
+impl HexDump {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<'a>(title: String, rows: &[HexRow], conf: &Config) -> Paragraph<'a> {
+        let style: Style = Style::default()
+            .fg(parse_to_color(&conf.color_schemes.fg).unwrap())
+            .bg(parse_to_color(&conf.color_schemes.bg).unwrap());
+        let dim: Style = Style::default().fg(parse_to_color(&conf.color_schemes.syntax.comment).unwrap());
+        let block: Block<'_> = Block::default()
+            .title(title)
+            .borders(Borders::ALL);
+
+        let lines: Vec<Line<'_>> = rows.iter().map(|row| {
+            let mut spans: Vec<Span<'_>> = vec![Span::styled(format!("{:08x}  ", row.offset), dim)];
+            for i in 0..16 {
+                let gap = if i == 7 { "  " } else { " " };
+                spans.push(match row.bytes.get(i) {
+                    Some(0) => Span::styled(format!("00{gap}"), dim),
+                    Some(b) => Span::raw(format!("{b:02x}{gap}")),
+                    None => Span::raw(format!("  {gap}")),
+                });
+            }
+            spans.push(Span::raw(" "));
+            for b in &row.bytes {
+                spans.push(match b.is_ascii_graphic() || *b == b' ' {
+                    true => Span::raw((*b as char).to_string()),
+                    false => Span::styled(".", dim),
+                });
+            }
+            Line::from(spans)
+        }).collect();
+
+        let hex_dump: Paragraph<'_> = Paragraph::new(lines)
+            .style(style)
+            .block(block);
+        hex_dump
+    }
+}
+
 pub struct UiTree<'a> {
     pub bg: Color,
     pub fg: Color,
@@ -92,46 +244,79 @@ pub struct UiTree<'a> {
     pub headers: Row<'a>,
     pub items: Vec<Row<'a>>,
     pub highlight_sym: &'a str,
+    /// Wider in the tree view, to leave room for the indentation.
+    pub name_width: u16,
 }
 
 impl<'a> Clone for UiTree<'a> {
     fn clone(&self) -> Self {
         Self {
-            bg: self.bg.clone(),
-            fg: self.fg.clone(),
+            bg: self.bg,
+            fg: self.fg,
             title: self.title.clone(),
             state: self.state.clone(),
             headers: self.headers.clone(),
             items: self.items.clone(),
-            highlight_sym: self.highlight_sym.clone(),
-
+            highlight_sym: self.highlight_sym,
+            name_width: self.name_width,
         }
     }
 }
 
 impl<'a> UiTree<'a> {
-    pub fn new(dir: &mut Directory, conf: &Config) -> Self {
+    pub fn new(dir: &mut Directory, marked: &[usize], editing: Option<(usize, &AppShell)>, conf: &Config) -> Self {
+        let marked_style: Style = Style::default()
+            .fg(parse_to_color(&conf.color_schemes.syntax.attribute).unwrap())
+            .add_modifier(Modifier::BOLD);
         Self {
             bg: parse_to_color(&conf.color_schemes.bg).unwrap(),
             fg: parse_to_color(&conf.color_schemes.fg).unwrap(),
-            title: format!("Walk through {}", dir.display()),
+            title: match dir.tree_depth() {
+                Some(_) => format!("Tree of {}", dir.display()),
+                None => format!("Walk through {}", dir.display()),
+            },
             state: TableState::default().with_selected(Some(0)),
             headers: Row::new(vec!["Name", "Date modified", "Type", "Size"]),
             items: {
                 let items = dir.walk();
-                let rows = items.iter().map(|r| Row::new(r.to_vec())).collect::<Vec<Row>>();
+                let mut rows = items.iter().enumerate().map(|(idx, r)| match marked.contains(&idx) {
+                    true => Row::new(r.to_vec()).style(marked_style),
+                    false => Row::new(r.to_vec()),
+                }).collect::<Vec<Row>>();
+                if let Some((idx, editor)) = editing.filter(|(idx, _)| *idx < items.len()) {
+                    let name_len = dir.get(idx).map(|n| n.name().to_string_lossy().len()).unwrap_or(0);
+                    let mut cells = items[idx].to_vec();
+                    let indent = cells[0][..cells[0].len() - name_len].to_owned();
+                    let name = Self::editor_line(indent, editor);
+                    rows[idx] = Row::new(std::iter::once(Cell::from(name)).chain(cells.drain(1..).map(Cell::from)));
+                }
                 rows
             },
-            highlight_sym: " > "
+            highlight_sym: " > ",
+            name_width: if dir.tree_depth().is_some() { 40 } else { 25 },
         }
     }
 
+    /// The name being edited, with the char under the cursor picked out.
+    fn editor_line(indent: String, editor: &AppShell) -> Line<'a> {
+        let (before, after) = editor.input.split_at(editor.cursor_position);
+        let mut rest = after.chars();
+        let under: String = rest.next().map(String::from).unwrap_or(" ".to_string());
+        let edited = Style::default().add_modifier(Modifier::UNDERLINED);
+        Line::from(vec![
+            Span::raw(indent),
+            Span::styled(before.to_owned(), edited),
+            Span::styled(under, edited.add_modifier(Modifier::REVERSED)),
+            Span::styled(rest.as_str().to_owned(), edited),
+        ])
+    }
+
     pub fn render(&self) -> Table<'_> {
-        let items = (&self.items).to_owned();
-        let title = (&self).title.to_owned();
-        let headers = (&self).headers.to_owned();
+        let items = self.items.to_owned();
+        let title = self.title.to_owned();
+        let headers = self.headers.to_owned();
         let widths = [
-            Constraint::Length(25),
+            Constraint::Length(self.name_width),
             Constraint::Length(20),
             Constraint::Length(10),
             Constraint::Length(10)
@@ -158,19 +343,52 @@ pub struct FileSystemUi<'a> {
 }
 
 impl<'a> FileSystemUi<'a> {
-    pub fn new(dir: &mut Directory, conf: &Config) -> Self {
-        let tree = UiTree::new(dir, conf);
+    pub fn new(dir: &mut Directory, loaded: Option<&(Arc<PathBuf>, Preview)>, marked: &[usize], editing: Option<(usize, &AppShell)>, conf: &Config) -> Self {
+        let tree = UiTree::new(dir, marked, editing, conf);
         let style = Style::default()
             .fg(parse_to_color(&conf.color_schemes.fg).unwrap())
             .bg(parse_to_color(&conf.color_schemes.bg).unwrap());
-        let block = Block::default().borders(Borders::ALL);
-        let preview = Paragraph::new("").style(style).block(block);
+        let mut block = Block::default().borders(Borders::ALL);
+        let lines: Vec<Line<'_>> = match loaded {
+            Some((path, preview)) => {
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                let (title, lines) = Self::preview_lines(preview, &Theme::new(&conf.color_schemes.syntax));
+                block = block.title(format!("{name} {title}"));
+                lines
+            },
+            None => vec![],
+        };
+        let preview = Paragraph::new(lines).style(style).block(block);
 
         Self {
             tree,
             preview
         }
     }
+
+    fn preview_lines(preview: &Preview, theme: &Theme) -> (String, Vec<Line<'a>>) {
+        match preview {
+            Preview::Text { encoding, lines, highlights: Some(highlights) } => (
+                format!("[{encoding}]"),
+                lines.iter().zip(highlights).map(|(l, h)| theme.line(l, h)).collect()
+            ),
+            Preview::Text { encoding, lines, highlights: None } => (
+                format!("[{encoding}]"),
+                lines.iter().map(|l| Line::from(l.to_owned())).collect()
+            ),
+            Preview::Listing(children) => (
+                "[Folder]".to_string(),
+                children.iter().map(|c| Line::from(c.to_owned())).collect()
+            ),
+            Preview::Metadata(fields) | Preview::Binary(fields) => (
+                "[Info]".to_string(),
+                fields.iter().map(|(k, v)| Line::from(vec![
+                    Span::styled(format!("{k:<12}"), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(v.to_owned())
+                ])).collect()
+            ),
+        }
+    }
 }
 
 pub enum LayoutOps {
@@ -239,17 +457,230 @@ impl Layouts {
 
 fn draw_bars(f: &mut Frame, app: &mut App, area: &Rc<[Rect]>) {
     let mode_bar = ModeBar::new(app.format_mode(), &app.config);
-    let shell = Shell::new((&app.shell.input).to_owned(), &app.config);
+    let shell = Shell::new(&app.shell, app.prompt.as_ref().map(|p| p.question.as_str()), &app.config);
     f.render_widget(mode_bar, area[1]);
     f.render_widget(shell, area[2]);
 }
 
 fn draw_main(f: &mut Frame, app: &mut App, area: &Rc<[Rect]>) {
-    let main_view = FileSystemUi::new(&mut app.cwd, &app.config);
+    app.refresh_preview();
+    let editing = app.selection_idx.zip(app.rename.as_ref().map(|r| &r.editor));
+    let marked: Vec<usize> = app.marked_rows();
+    let main_view = FileSystemUi::new(&mut app.cwd, app.preview.as_ref(), &marked, editing, &app.config);
     let mut tree = main_view.tree;
     tree.state.select(app.selection_idx);
     f.render_stateful_widget(tree.clone().render(), area[0], &mut tree.state);
-    f.render_widget(main_view.preview, area[1]);
+
+    let rows = area[1].height.saturating_sub(2);
+    app.hex.page = rows.max(1) as u64;
+    match app.hex_window(rows as usize) {
+        Some(Ok(window)) => {
+            let name = app.selected().map(|n| n.name().to_string_lossy().to_string()).unwrap_or_default();
+            f.render_widget(HexDump::new(format!("{name} [Hex]"), &window, &app.config), area[1]);
+        },
+        Some(Err(e)) => {
+            let style = Style::default()
+                .fg(parse_to_color(&app.config.color_schemes.fg).unwrap())
+                .bg(parse_to_color(&app.config.color_schemes.bg).unwrap());
+            let error = Paragraph::new(e.to_string()).style(style).block(Block::default().borders(Borders::ALL));
+            f.render_widget(error, area[1]);
+        },
+        None => f.render_widget(main_view.preview, area[1]),
+    }
+}
+
+/// Drops the first `n` chars of `line`, what is left keeps its styles.
+fn skip_chars(line: Line<'_>, mut n: usize) -> Line<'static> {
+    let spans: Vec<Span<'static>> = line.spans.into_iter().filter_map(|span| {
+        let count = span.content.chars().count();
+        if n >= count {
+            n -= count;
+            return None;
+        }
+        let rest: String = span.content.chars().skip(n).collect();
+        n = 0;
+        Some(Span::styled(rest, span.style))
+    }).collect();
+    Line::from(spans)
+}
+
+fn draw_viewer(f: &mut Frame, app: &mut App, area: Rect) {
+    let style = Style::default()
+        .fg(parse_to_color(&app.config.color_schemes.fg).unwrap())
+        .bg(parse_to_color(&app.config.color_schemes.bg).unwrap());
+    let gutter = Style::default().fg(parse_to_color(&app.config.color_schemes.syntax.comment).unwrap());
+    let matched = Style::default().add_modifier(Modifier::REVERSED);
+    let theme = Theme::new(&app.config.color_schemes.syntax);
+    let viewer = match &mut app.viewer {
+        Some(viewer) => viewer,
+        None => return,
+    };
+    viewer.page = area.height.saturating_sub(2).max(1) as usize;
+
+    let width: usize = viewer.lines.len().to_string().len();
+    let lines: Vec<Line<'_>> = viewer.lines.iter().enumerate().skip(viewer.scroll).take(viewer.page).map(|(i, text)| {
+        let found = viewer.find_in(text);
+        let content: Line<'_> = match (found.is_empty(), &viewer.highlights) {
+            (false, _) => {
+                let mut spans: Vec<Span<'_>> = Vec::new();
+                let mut cursor = 0;
+                for (start, end) in found {
+                    spans.push(Span::raw(text[cursor..start].to_owned()));
+                    spans.push(Span::styled(text[start..end].to_owned(), matched));
+                    cursor = end;
+                }
+                spans.push(Span::raw(text[cursor..].to_owned()));
+                Line::from(spans)
+            },
+            (true, Some(highlights)) => theme.line(text, highlights.get(i).map(Vec::as_slice).unwrap_or_default()),
+            (true, None) => Line::from(text.to_owned()),
+        };
+        let mut spans: Vec<Span<'_>> = vec![Span::styled(format!("{:>width$} ", i + 1), gutter)];
+        spans.extend(skip_chars(content, viewer.side).spans);
+        Line::from(spans)
+    }).collect();
+
+    let name = viewer.path.file_name().unwrap_or(viewer.path.as_os_str()).to_string_lossy();
+    let title: String = match &viewer.typing {
+        Some(typing) => format!("{name} /{typing}_"),
+        None => format!("{name} [{}] - /: search, n/N: next/previous, w: wrap, Esc: back", viewer.encoding),
+    };
+    let mut text = Paragraph::new(lines)
+        .style(style)
+        .block(Block::default().title(title).borders(Borders::ALL));
+    if viewer.wrap {
+        text = text.wrap(Wrap { trim: false });
+    }
+    f.render_widget(text, area);
+}
+
+/// What `brn` is about to do, old names struck through next to the new ones.
+fn draw_renames(f: &mut Frame, app: &App, renames: &[(PathBuf, PathBuf)], area: Rect) {
+    let style = Style::default()
+        .fg(parse_to_color(&app.config.color_schemes.fg).unwrap())
+        .bg(parse_to_color(&app.config.color_schemes.bg).unwrap());
+    let name = |path: &Path| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string();
+    let rows: Vec<Row<'_>> = renames.iter().map(|(from, to)| Row::new(vec![
+        Cell::from(format!("- {}", name(from))).style(Style::default().fg(Color::Red).add_modifier(Modifier::CROSSED_OUT)),
+        Cell::from(format!("+ {}", name(to))).style(Style::default().fg(Color::Green)),
+    ])).collect();
+    let table = Table::new(rows, [Constraint::Percentage(50), Constraint::Percentage(50)])
+        .style(style)
+        .block(Block::default().title(format!("Rename in {} - y: apply, n: cancel", app.cwd.display())).borders(Borders::ALL))
+        .header(Row::new(vec!["Name", "New name"]).add_modifier(Modifier::BOLD));
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+fn draw_trash(f: &mut Frame, app: &mut App, area: Rect) {
+    let style = Style::default()
+        .fg(parse_to_color(&app.config.color_schemes.fg).unwrap())
+        .bg(parse_to_color(&app.config.color_schemes.bg).unwrap());
+    let rows: Vec<Row<'_>> = app.trash.items.iter().map(|item| Row::new(vec![
+        item.name.to_owned(),
+        item.original.display().to_string(),
+        item.deleted.to_owned(),
+    ])).collect();
+    let widths = [
+        Constraint::Length(25),
+        Constraint::Min(30),
+        Constraint::Length(20),
+    ];
+    let block = Block::default()
+        .title("Trash - r: restore, d: delete, E: empty, Esc: back")
+        .borders(Borders::ALL);
+    let table = Table::new(rows, widths)
+        .style(style)
+        .block(block)
+        .header(Row::new(vec!["Name", "Original path", "Deleted"]).add_modifier(Modifier::BOLD))
+        .highlight_symbol(" > ")
+        .highlight_spacing(HighlightSpacing::Always);
+    let mut state = TableState::default().with_selected(app.trash.selection_idx);
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+/// `./folder` relative to where the search started, `.` for the start itself.
+fn relative(path: &Path, root: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(p) if p.as_os_str().is_empty() => ".".to_string(),
+        Ok(p) => format!("./{}", p.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+fn draw_search(f: &mut Frame, app: &mut App, area: Rect) {
+    let style = Style::default()
+        .fg(parse_to_color(&app.config.color_schemes.fg).unwrap())
+        .bg(parse_to_color(&app.config.color_schemes.bg).unwrap());
+    let view = Layouts::new(LayoutOps::View, area);
+    let view_rects = view.rects();
+    let context: Option<(usize, Vec<String>)> = app.match_context().map(|(first, lines)| (first, lines.to_vec()));
+    let search = match &app.search {
+        Some(search) => search,
+        None => return,
+    };
+    let matched = Style::default().add_modifier(Modifier::REVERSED);
+
+    let (header, widths, rows): (Vec<&str>, Vec<Constraint>, Vec<Row<'_>>) = match search.contents {
+        true => (
+            vec!["File", "Line", "Match"],
+            vec![Constraint::Percentage(35), Constraint::Length(6), Constraint::Percentage(65)],
+            search.results.iter().map(|found| {
+                let file = relative(&found.path, &search.root);
+                let (number, snippet) = match &found.line {
+                    Some(line) => (line.number.to_string(), Line::from(vec![
+                        Span::raw(line.snippet[..line.start].to_owned()),
+                        Span::styled(line.snippet[line.start..line.end].to_owned(), matched),
+                        Span::raw(line.snippet[line.end..].to_owned()),
+                    ])),
+                    None => (String::new(), Line::default()),
+                };
+                Row::new(vec![Cell::from(file), Cell::from(number), Cell::from(snippet)])
+            }).collect()
+        ),
+        false => (
+            vec!["Name", "Folder"],
+            vec![Constraint::Percentage(40), Constraint::Percentage(60)],
+            search.results.iter().map(|found| {
+                let name = found.path.file_name().unwrap_or(found.path.as_os_str()).to_string_lossy().to_string();
+                let folder = found.path.parent().map(|p| relative(p, &search.root)).unwrap_or_default();
+                Row::new(vec![name, folder])
+            }).collect()
+        ),
+    };
+    let block = Block::default()
+        .title(format!("Search {} - Enter: go to, Esc: back", search.pattern))
+        .borders(Borders::ALL);
+    let table = Table::new(rows, widths)
+        .style(style)
+        .block(block)
+        .header(Row::new(header).add_modifier(Modifier::BOLD))
+        .highlight_symbol(" > ")
+        .highlight_spacing(HighlightSpacing::Always);
+    let mut state = TableState::default().with_selected(search.selection_idx);
+    if !search.contents {
+        f.render_stateful_widget(table, area, &mut state);
+        return;
+    }
+    f.render_stateful_widget(table, view_rects[0], &mut state);
+
+    // The file around the selected match, with the matching line standing out.
+    let selected = search.selected().and_then(|found| found.line.as_ref().map(|line| (&found.path, line.number)));
+    let (title, lines): (String, Vec<Line<'_>>) = match (selected, context) {
+        (Some((path, number)), Some((first, lines))) => (
+            format!("{} [Line {number}]", path.file_name().unwrap_or(path.as_os_str()).to_string_lossy()),
+            lines.into_iter().enumerate().map(|(i, text)| {
+                let text = format!("{:>5} {text}", first + i);
+                match first + i == number {
+                    true => Line::styled(text, matched),
+                    false => Line::from(text),
+                }
+            }).collect()
+        ),
+        _ => (String::new(), Vec::new()),
+    };
+    let preview = Paragraph::new(lines).style(style).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(preview, view_rects[1]);
 }
 
 fn draw_options(f: &mut Frame, app: &mut App, area: &Rc<[Rect]>) {
@@ -257,6 +688,17 @@ fn draw_options(f: &mut Frame, app: &mut App, area: &Rc<[Rect]>) {
     f.render_widget(options_list, area[1])
 }
 
+fn draw_finder(f: &mut Frame, app: &mut App, area: &Rc<[Rect]>) {
+    let finder = match &app.finder {
+        Some(finder) => finder,
+        None => return,
+    };
+    let list = Finder::new(finder, &app.config);
+    let mut state = ListState::default().with_selected(finder.selection_idx);
+    f.render_widget(Clear, area[1]);
+    f.render_stateful_widget(list, area[1], &mut state)
+}
+
 pub(crate) fn user_interface(f: &mut Frame, app: &mut App) {
     let screen: Rect = f.size();
     let root = Layouts::new(LayoutOps::App, screen);
@@ -265,13 +707,24 @@ pub(crate) fn user_interface(f: &mut Frame, app: &mut App) {
     let secondary_rects = secondary_area.rects();
     let options_area = Layouts::new(LayoutOps::Options(40, 27), root_rects[0]);
     let options_rects = options_area.rects();
+    let finder_area = Layouts::new(LayoutOps::Options(60, 60), root_rects[0]);
+    let finder_rects = finder_area.rects();
 
     // Draw all layouts.
-    draw_main(f, app, secondary_rects);
+    match app.shown_mode() {
+        Mode::InsideTrash => draw_trash(f, app, root_rects[0]),
+        Mode::InsideSearch => draw_search(f, app, root_rects[0]),
+        Mode::InsideViewer => draw_viewer(f, app, root_rects[0]),
+        _ => draw_main(f, app, secondary_rects),
+    }
+    if let Some(Pending::BulkRename { renames, .. }) = app.prompt.as_ref().map(|p| &p.pending) {
+        draw_renames(f, app, renames, root_rects[0]);
+    }
     draw_bars(f, app, root_rects);
     match app.mode {
         Mode::InsideOptions => draw_options(f, app, options_rects),
-        _ => {}
+        Mode::InsideFinder => draw_finder(f, app, finder_rects),
+        _ => {},
     }
 }
 